@@ -0,0 +1,494 @@
+const test = require('node:test')
+const assert = require('node:assert')
+const fs = require('node:fs')
+const os = require('node:os')
+const path = require('node:path')
+const { Database } = require('../index.js')
+
+test('aborting an async query interrupts it and rejects', async () => {
+  const db = new Database(':memory:')
+  const ac = new AbortController()
+  const endless = db.queryAsync(
+    'WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c',
+    [],
+    { signal: ac.signal },
+  )
+  setTimeout(() => ac.abort(), 50)
+  await assert.rejects(endless, (e) => e.code === 'Cancelled' && /aborted/.test(e.message))
+  assert.throws(() => db.queryAsync('SELECT 1', [], { signal: AbortSignal.abort() }), (e) => e.code === 'Cancelled')
+  assert.deepStrictEqual(await db.queryAsync('SELECT 1 AS one'), [{ one: 1 }])
+})
+
+test('Database.open shares one connection per file', () => {
+  const dir = fs.mkdtempSync(path.join(os.tmpdir(), 'rustite-'))
+  try {
+    // The file doesn't exist for the first open
+    const file = path.join(dir, 'shared.db')
+    const a = Database.open(file)
+    a.execute('CREATE TABLE t (id INTEGER PRIMARY KEY)')
+    const b = Database.open(path.join(dir, '.', 'shared.db'))
+    a.begin()
+    assert.strictEqual(b.inTransaction(), true)
+    a.rollback()
+
+    b.withCte('r', 'SELECT 1 AS x').close()
+    assert.strictEqual(b.table('t').count(), 0)
+    a.close()
+    assert.strictEqual(b.table('t').count(), 0)
+    b.close()
+    assert.throws(() => b.table('t').count(), /database is closed/)
+  } finally {
+    fs.rmSync(dir, { recursive: true, force: true })
+  }
+})
+
+test('closing a withCte handle leaves the parent connection open', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY)')
+  db.table('t').insert({ id: 1 })
+  const derived = db.withCte('r', 'SELECT 1 AS x')
+  derived.close()
+  assert.throws(() => derived.query('SELECT x FROM r'), /database is closed/)
+  assert.strictEqual(db.table('t').count(), 1)
+})
+
+test('calls fail once the database is closed', async () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY)')
+  const t = db.table('t')
+  db.close()
+  db.close()
+  assert.throws(() => db.query('SELECT 1'), /database is closed/)
+  assert.throws(() => db.table('t'), /database is closed/)
+  assert.throws(() => t.count(), /database is closed/)
+  assert.throws(() => t.insert({ id: 1 }), /database is closed/)
+  await assert.rejects(t.allAsync(), /database is closed/)
+})
+
+test('numbered placeholders reuse one parameter', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE items (id INTEGER PRIMARY KEY, a TEXT, b TEXT, v INTEGER)')
+  db.execute('INSERT INTO items (a, b) VALUES (?1, ?1)', ['x'])
+  assert.deepStrictEqual(db.query('SELECT id FROM items WHERE a = ?1 AND b = ?1', ['x']), [{ id: 1 }])
+})
+
+test('execute with params rejects more than one statement', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE items (id INTEGER PRIMARY KEY, v INTEGER); INSERT INTO items (v) VALUES (0)')
+  assert.throws(() => db.execute('UPDATE items SET v = ?; DELETE FROM items', [1]), /single statement/)
+  assert.deepStrictEqual(db.query('SELECT v FROM items'), [{ v: 0 }])
+  db.execute('UPDATE items SET v = ?; -- trailing comment\n', [1])
+  assert.deepStrictEqual(db.query('SELECT v FROM items'), [{ v: 1 }])
+})
+
+test('starting an abortable query does not wait for a running one', async () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY)')
+  const slow = db.queryAsync(
+    'WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 3000000) SELECT count(*) AS n FROM c',
+  )
+  // Give the worker a moment to take the connection lock
+  await new Promise((resolve) => setTimeout(resolve, 20))
+  const ac = new AbortController()
+  const started = Date.now()
+  const queued = db.table('u').allAsync({ signal: ac.signal })
+  assert.ok(Date.now() - started < 50, 'allAsync blocked the event loop')
+  assert.deepStrictEqual(await slow, [{ n: 3000000 }])
+  assert.deepStrictEqual(await queued, [])
+})
+
+test('queryReadonly rejects statements that write', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, g TEXT)')
+  db.table('t').insert([{ g: 'a' }, { g: 'b' }])
+  assert.deepStrictEqual(db.queryReadonly('SELECT * FROM t WHERE g = ?', ['b']), [{ id: 2, g: 'b' }])
+  assert.throws(() => db.queryReadonly('DELETE FROM t'), /not read-only/)
+  assert.strictEqual(db.table('t').count(), 2)
+})
+
+test('transaction reports the rows changed inside it', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, g TEXT)')
+  const res = db.transaction(() => {
+    db.table('t').insert([{ g: 'a' }, { g: 'a' }, { g: 'b' }])
+    db.table('t').where('g', 'a').update({ g: 'c' })
+    return 'done'
+  })
+  assert.deepStrictEqual(res, { result: 'done', changes: 5 })
+  assert.throws(
+    () =>
+      db.transaction(() => {
+        db.table('t').insert({ g: 'x' })
+        throw new Error('boom')
+      }),
+    /boom/,
+  )
+  assert.strictEqual(db.table('t').count(), 3)
+})
+
+test('describe reports the columns and parameters of a SELECT', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, name TEXT, score REAL)')
+  assert.deepStrictEqual(db.describe('SELECT id, name, score * 2 AS s FROM u WHERE id > ? AND name = ?'), {
+    columns: [{ name: 'id', declType: 'INTEGER' }, { name: 'name', declType: 'TEXT' }, { name: 's' }],
+    paramCount: 2,
+  })
+})
+
+test('Dates bind in query parameters', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE ev (id INTEGER PRIMARY KEY, at TEXT)')
+  const d = new Date('2024-05-06T07:08:09.000Z')
+  db.table('ev').insert([{ at: d }, { at: new Date(0) }])
+  assert.deepStrictEqual(db.query('SELECT * FROM ev WHERE at = ?', [d]), [{ id: 1, at: '2024-05-06T07:08:09.000Z' }])
+  assert.deepStrictEqual(new Database(':memory:', { dateFormat: 'unix' }).query('SELECT ? AS v', [d]), [{ v: 1714979289 }])
+  assert.throws(() => new Database(':memory:', { dateFormat: 'x' }), /Unknown date format/)
+})
+
+test('tables lists user tables but not internal ones', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE b (id INTEGER PRIMARY KEY AUTOINCREMENT); CREATE TABLE a (x); CREATE VIEW v AS SELECT * FROM a')
+  db.table('b').insert({ id: 1 })
+  assert.deepStrictEqual(db.tables(), ['a', 'b'])
+  db.execute('CREATE TABLE sqlitex (y)')
+  assert.deepStrictEqual(db.tables(), ['a', 'b', 'sqlitex'])
+  assert.deepStrictEqual(db.views(), ['v'])
+})
+
+test('each mutex mode opens a working connection', () => {
+  for (const mutex of [undefined, 'no', 'full']) {
+    assert.deepStrictEqual(new Database(':memory:', { mutex }).query('SELECT 1 AS one'), [{ one: 1 }])
+  }
+  assert.throws(() => new Database(':memory:', { mutex: 'x' }), /Unknown mutex mode 'x'/)
+})
+
+test('immediate transactions on two connections serialize', () => {
+  const dir = fs.mkdtempSync(path.join(os.tmpdir(), 'rustite-'))
+  try {
+    const file = path.join(dir, 'counter.db')
+    const a = new Database(file)
+    const b = new Database(file)
+    a.execute('CREATE TABLE c (n INTEGER)')
+    a.execute('INSERT INTO c VALUES (0)')
+    a.begin('immediate')
+    // The second writer is turned away at BEGIN instead of deadlocking on a lock upgrade later
+    assert.throws(() => b.begin('immediate'), /database is locked/)
+    a.execute('UPDATE c SET n = n + 1')
+    a.commit()
+    b.transaction(() => b.execute('UPDATE c SET n = n + 1'), 'immediate')
+    assert.deepStrictEqual(a.query('SELECT n FROM c'), [{ n: 2 }])
+    assert.throws(() => a.begin('bogus'), /Unknown transaction behavior/)
+    a.close()
+    b.close()
+  } finally {
+    fs.rmSync(dir, { recursive: true, force: true })
+  }
+})
+
+test('migrate applies each migration once', () => {
+  const db = new Database(':memory:')
+  const migrations = [
+    { name: 'create_users', up: (db) => db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)') },
+    { name: 'add_email', up: (db) => db.table('users').addColumn('email', 'TEXT') },
+  ]
+  assert.deepStrictEqual(db.migrate(migrations), ['create_users', 'add_email'])
+  assert.deepStrictEqual(db.migrate(migrations), [])
+  assert.deepStrictEqual(db.query('SELECT name FROM _migrations ORDER BY rowid'), [
+    { name: 'create_users' },
+    { name: 'add_email' },
+  ])
+  const failing = {
+    name: 'broken',
+    up: (db) => {
+      db.execute('CREATE TABLE half (id INTEGER)')
+      throw new Error('boom')
+    },
+  }
+  assert.throws(() => db.migrate([...migrations, failing]), /Migration 'broken' failed: .*boom/)
+  assert.deepStrictEqual(db.tables(), ['_migrations', 'users'])
+})
+
+test('queryWith maps rows, parsing a JSON column', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE docs (id INTEGER PRIMARY KEY, body TEXT)')
+  db.table('docs').insert([{ body: '{"tags":["a","b"]}' }, { body: '{"tags":[]}' }])
+  const rows = db.queryWith('SELECT id, body FROM docs WHERE id > ?', [0], (columns) =>
+    Object.fromEntries(columns.map(([name, value]) => [name, name === 'body' ? JSON.parse(value) : value])),
+  )
+  assert.deepStrictEqual(rows, [
+    { id: 1, body: { tags: ['a', 'b'] } },
+    { id: 2, body: { tags: [] } },
+  ])
+})
+
+test('query binds positional and named params and rejects a mismatch', () => {
+  const db = new Database(':memory:')
+  assert.deepStrictEqual(db.query('SELECT ? AS a, ? AS b', [1, 2]), [{ a: 1, b: 2 }])
+  assert.deepStrictEqual(db.query('SELECT :a AS a, @b AS b, $c AS c', { a: 1, b: 2, c: 3 }), [{ a: 1, b: 2, c: 3 }])
+  assert.throws(() => db.query('SELECT ? AS a', { a: 1 }), /SQL uses positional parameters but params is an object/)
+  assert.throws(() => db.query('SELECT :a AS a', [1]), /SQL uses named parameter ':a' but params is an array/)
+  assert.throws(() => db.query('SELECT :a AS a', { b: 1 }), /Missing value for named parameter ':a'/)
+  assert.throws(() => db.query('SELECT ? AS a', [1, 2]), /SQL expects 1 parameter\(s\) but 2 were given/)
+})
+
+test('a new database can be created as UTF-16', () => {
+  const db = new Database(':memory:', { encoding: 'UTF-16le' })
+  assert.deepStrictEqual(db.query('PRAGMA encoding'), [{ encoding: 'UTF-16le' }])
+  db.execute('CREATE TABLE t (s TEXT)')
+  db.table('t').insert({ s: 'héllo' })
+  assert.deepStrictEqual(db.query('SELECT s FROM t'), [{ s: 'héllo' }])
+  assert.deepStrictEqual(new Database(':memory:').query('PRAGMA encoding'), [{ encoding: 'UTF-8' }])
+  assert.throws(() => new Database(':memory:', { encoding: 'latin1' }), /Unknown encoding 'latin1'/)
+
+  const dir = fs.mkdtempSync(path.join(os.tmpdir(), 'rustite-'))
+  try {
+    const file = path.join(dir, 'utf8.db')
+    const existing = new Database(file)
+    existing.execute('CREATE TABLE t (s TEXT)')
+    existing.close()
+    assert.throws(() => new Database(file, { encoding: 'UTF-16' }))
+  } finally {
+    fs.rmSync(dir, { recursive: true, force: true })
+  }
+})
+
+test('insert inside a manual transaction does not nest BEGIN', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, g TEXT)')
+  assert.strictEqual(db.inTransaction(), false)
+  db.begin()
+  assert.strictEqual(db.inTransaction(), true)
+  assert.deepStrictEqual(db.table('t').insert([{ g: 'a' }, { g: 'b' }]), [1, 2])
+  assert.strictEqual(db.inTransaction(), true)
+  db.commit()
+  assert.strictEqual(db.inTransaction(), false)
+  assert.strictEqual(db.table('t').count(), 2)
+})
+
+test('inserts in a user transaction roll back with it', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, g TEXT UNIQUE)')
+  db.table('t').insert({ g: 'kept' })
+  db.begin()
+  db.table('t').insert([{ g: 'a' }, { g: 'b' }])
+  // A failing batch only undoes its own rows and leaves the transaction open
+  assert.throws(() => db.table('t').insert([{ g: 'c' }, { g: 'a' }]), /UNIQUE/)
+  assert.strictEqual(db.table('t').count(), 3)
+  db.rollback()
+  assert.deepStrictEqual(db.query('SELECT g FROM t'), [{ g: 'kept' }])
+})
+
+test('cached reads are not re-run until a write', () => {
+  const db = new Database(':memory:', { cacheSize: 10, cacheTtlMs: 60000 })
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, g TEXT)')
+  // random() changes on every run, so an equal result means the query wasn't executed again
+  const first = db.query('SELECT random() AS r')
+  assert.deepStrictEqual(db.query('SELECT random() AS r'), first)
+  assert.strictEqual(db.cacheSize(), 1)
+  db.table('t').insert({ g: 'x' })
+  assert.notDeepStrictEqual(db.query('SELECT random() AS r'), first)
+  const again = db.query('SELECT random() AS r')
+  db.clearCache()
+  assert.strictEqual(db.cacheSize(), 0)
+  assert.notDeepStrictEqual(db.query('SELECT random() AS r'), again)
+  const uncached = new Database(':memory:')
+  assert.notDeepStrictEqual(uncached.query('SELECT random() AS r'), uncached.query('SELECT random() AS r'))
+})
+
+test('withTypes reports the storage class of each column', () => {
+  const db = new Database(':memory:')
+  const { columns, rows } = db.query("SELECT 1 AS i, 1.5 AS r, 'x' AS t, x'00' AS b, NULL AS n", [], { withTypes: true })
+  assert.deepStrictEqual(columns, [
+    { name: 'i', type: 'INTEGER' },
+    { name: 'r', type: 'REAL' },
+    { name: 't', type: 'TEXT' },
+    { name: 'b', type: 'BLOB' },
+    { name: 'n', type: 'NULL' },
+  ])
+  assert.strictEqual(rows.length, 1)
+  assert.deepStrictEqual(db.query('SELECT 1 AS i WHERE 0', [], { withTypes: true }), {
+    columns: [{ name: 'i', type: 'NULL' }],
+    rows: [],
+  })
+})
+
+test('queryJson serializes a row with a blob to compact JSON', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE f (id INTEGER PRIMARY KEY, name TEXT, data BLOB, r REAL)')
+  db.execute(`INSERT INTO f (name, data, r) VALUES ('a"b\n', x'48656c6c6f', 1.5), ('e', x'00ff10', NULL)`)
+  const json = db.queryJson('SELECT * FROM f')
+  assert.strictEqual(json, '[{"id":1,"name":"a\\"b\\n","data":"SGVsbG8=","r":1.5},{"id":2,"name":"e","data":"AP8Q","r":null}]')
+  assert.strictEqual(Buffer.from(JSON.parse(json)[0].data, 'base64').toString(), 'Hello')
+  assert.strictEqual(db.queryJson('SELECT data FROM f WHERE id = ?', [2], { blobEncoding: 'hex' }), '[{"data":"00ff10"}]')
+  assert.strictEqual(db.queryJson('SELECT data FROM f WHERE id = ?', [2], { blobEncoding: 'array' }), '[{"data":[0,255,16]}]')
+  assert.throws(() => db.queryJson('SELECT 1', [], { blobEncoding: 'x' }), /Unknown blob encoding 'x'/)
+})
+
+test('pragmaRows returns pragma tables and rejects bad names', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY)')
+  db.execute('CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id))')
+  const fks = db.pragmaRows('foreign_key_list', 'posts')
+  assert.strictEqual(fks[0].table, 'users')
+  assert.strictEqual(fks[0].from, 'user_id')
+  assert.deepStrictEqual(
+    db.pragmaRows('table_info', 'posts').map((c) => c.name),
+    ['id', 'user_id'],
+  )
+  assert.throws(() => db.pragmaRows('x; DROP TABLE users'), /Invalid pragma name/)
+})
+
+test('query returns an empty result for statements without columns', () => {
+  const db = new Database(':memory:')
+  assert.deepStrictEqual(db.query('CREATE TABLE t (id INTEGER PRIMARY KEY)'), [])
+  assert.deepStrictEqual(db.query('INSERT INTO t DEFAULT VALUES'), [])
+  assert.deepStrictEqual(db.query('CREATE INDEX i ON t(id)', [], { withTypes: true }), { columns: [], rows: [] })
+  assert.strictEqual(db.queryJson('DROP INDEX i'), '[]')
+  assert.strictEqual(db.table('t').all().length, 1)
+})
+
+test('numbersAsStrings returns numeric columns as strings', () => {
+  const db = new Database(':memory:', { numbersAsStrings: true })
+  db.execute('CREATE TABLE p (id INTEGER PRIMARY KEY, price REAL, big INTEGER)')
+  db.table('p').insert({ price: 19.99, big: 1 })
+  db.execute('UPDATE p SET big = 9007199254740993')
+  const row = db.table('p').first()
+  assert.strictEqual(row.id, '1')
+  assert.strictEqual(row.price, '19.99')
+  assert.strictEqual(row.big, '9007199254740993')
+  assert.deepStrictEqual(new Database(':memory:').query('SELECT 1.5 AS x'), [{ x: 1.5 }])
+})
+
+test('reindex and optimize run and reject bad targets', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, email TEXT)')
+  db.execute('CREATE INDEX u_email ON u(email)')
+  db.table('u').insert([{ email: 'a' }, { email: 'b' }])
+  db.reindex()
+  db.reindex('u')
+  db.reindex('u_email')
+  db.optimize()
+  assert.throws(() => db.reindex('u; DROP TABLE u'), /Invalid reindex target/)
+  assert.throws(() => db.reindex('nope'), /unable to identify/)
+  assert.strictEqual(db.table('u').where('email', 'b').first().id, 2)
+})
+
+test('withTempRows joins against thousands of bound rows', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, region TEXT)')
+  db.execute(
+    `WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 20000)
+     INSERT INTO users (region) SELECT CASE i % 2 WHEN 0 THEN 'eu' ELSE 'us' END FROM n`,
+  )
+  const wanted = Array.from({ length: 5000 }, (_, i) => ({ user_id: i * 2 + 2, region: 'eu' }))
+  const keys = db.withTempRows('wanted', wanted)
+  assert.strictEqual(keys.count(), 5000)
+  const [{ n }] = db.query(
+    'SELECT count(*) AS n FROM users JOIN wanted ON wanted.user_id = users.id AND wanted.region = users.region',
+  )
+  assert.strictEqual(n, 5000)
+  assert.throws(() => db.withTempRows('wanted', [{ a: 1 }]), /already exists/)
+  assert.throws(() => db.withTempRows('bad name', [{ a: 1 }]), /Invalid temp table name/)
+  assert.throws(() => db.withTempRows('x', [{ a: 1 }, { b: 2 }]), /same columns/)
+})
+
+test('fileSize grows with data and freelistCount tracks freed pages', () => {
+  const db = new Database(':memory:')
+  const before = db.fileSize()
+  db.execute('CREATE TABLE blobs (id INTEGER PRIMARY KEY, data TEXT)')
+  db.execute(
+    `WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+     INSERT INTO blobs (data) SELECT hex(randomblob(500)) FROM n`,
+  )
+  assert.ok(db.fileSize() > before)
+  assert.strictEqual(db.freelistCount(), 0)
+  db.execute('DELETE FROM blobs')
+  assert.ok(db.freelistCount() > 0)
+})
+
+test('lockForUpdate keeps other connections from taking the row', () => {
+  const dir = fs.mkdtempSync(path.join(os.tmpdir(), 'rustite-'))
+  try {
+    const file = path.join(dir, 'lock.db')
+    const a = new Database(file)
+    const b = new Database(file)
+    a.execute('CREATE TABLE acct (id INTEGER PRIMARY KEY, balance INTEGER)')
+    a.table('acct').insert({ balance: 100 })
+    assert.throws(() => a.table('acct').where('id', 1).lockForUpdate(), /inside a transaction/)
+
+    a.begin()
+    const [row] = a.table('acct').where('id', 1).lockForUpdate()
+    b.begin()
+    assert.throws(() => b.table('acct').where('id', 1).lockForUpdate(), /locked/)
+    b.rollback()
+    a.table('acct').where('id', 1).update({ balance: row.balance - 30 })
+    a.commit()
+
+    b.begin('immediate')
+    assert.strictEqual(b.table('acct').where('id', 1).lockForUpdate()[0].balance, 70)
+    b.commit()
+    a.close()
+    b.close()
+  } finally {
+    fs.rmSync(dir, { recursive: true, force: true })
+  }
+})
+
+test('withoutForeignKeys loads cyclic rows and reports violations', () => {
+  const db = new Database(':memory:')
+  db.execute('PRAGMA foreign_keys = ON')
+  db.execute('CREATE TABLE a (id INTEGER PRIMARY KEY, b_id INTEGER REFERENCES b(id))')
+  db.execute('CREATE TABLE b (id INTEGER PRIMARY KEY, a_id INTEGER REFERENCES a(id))')
+  assert.throws(() => db.table('a').insert({ id: 1, b_id: 1 }), /FOREIGN KEY/)
+  const out = db.withoutForeignKeys(() => {
+    db.table('a').insert({ id: 1, b_id: 1 })
+    db.table('b').insert({ id: 1, a_id: 1 })
+    return 'loaded'
+  })
+  assert.strictEqual(out.result, 'loaded')
+  assert.deepStrictEqual(out.violations, [])
+  assert.strictEqual(db.query('PRAGMA foreign_keys')[0].foreign_keys, 1)
+
+  const bad = db.withoutForeignKeys(() => db.table('a').insert({ id: 3, b_id: 42 }))
+  assert.strictEqual(bad.violations.length, 1)
+  assert.strictEqual(bad.violations[0].parent, 'b')
+  assert.throws(() => db.withoutForeignKeys(() => {
+    throw new Error('boom')
+  }), /boom/)
+  assert.strictEqual(db.query('PRAGMA foreign_keys')[0].foreign_keys, 1)
+})
+
+test('getValue returns the first column of the first row', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT)')
+  assert.strictEqual(db.getValue('SELECT max(id) FROM t'), undefined)
+  assert.strictEqual(db.getValue('SELECT id FROM t'), null)
+  db.table('t').insert([{ name: 'a' }, { name: 'b' }, { name: 'c' }])
+  assert.strictEqual(db.getValue('SELECT max(id) FROM t'), 3)
+  assert.strictEqual(db.getValue('SELECT name FROM t WHERE id = ?', [2]), 'b')
+  assert.strictEqual(db.getValue('SELECT name FROM t WHERE id = :id', { id: 1 }), 'a')
+  assert.throws(() => db.getValue('CREATE TABLE x (a)'), /returns a column/)
+})
+
+test('vacuumInto writes a compacted copy that reopens', () => {
+  const dir = fs.mkdtempSync(path.join(os.tmpdir(), 'rustite-'))
+  try {
+    const src = path.join(dir, 'src.db')
+    const out = path.join(dir, "it's backup.db")
+    const db = new Database(src)
+    db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT)')
+    db.table('t').insert(Array.from({ length: 2000 }, () => ({ v: 'x'.repeat(200) })))
+    db.table('t').where('id', '>', 10).destroy()
+    db.vacuumInto(out)
+    assert.ok(fs.statSync(out).size < fs.statSync(src).size)
+    const copy = new Database(out)
+    assert.strictEqual(copy.table('t').count(), 10)
+    assert.strictEqual(copy.table('t').find(3).v, 'x'.repeat(200))
+    copy.close()
+    assert.throws(() => db.vacuumInto(out), /exists/)
+    assert.throws(() => db.transaction(() => db.vacuumInto(path.join(dir, 'b.db'))), /inside a transaction/)
+    db.close()
+  } finally {
+    fs.rmSync(dir, { recursive: true, force: true })
+  }
+})
@@ -0,0 +1,664 @@
+const test = require('node:test')
+const assert = require('node:assert')
+const { Database } = require('../index.js')
+
+function users() {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER, tags TEXT, at INTEGER)')
+  db.table('users').insert([
+    { name: 'ann', age: 30, tags: '["a"]', at: 10 },
+    { name: 'bob', age: 40, tags: '["b"]', at: 20 },
+  ])
+  return db
+}
+
+test('operators outside the supported list are rejected', () => {
+  const u = users().table('users')
+  assert.throws(() => u.where('name', "= 'zzz' OR 1=1 OR name =", 'q'), /Unsupported operator/)
+  assert.throws(() => u.whereFn('lower', ['name'], '= 1 OR 1=1 OR 1 =', 'x'), /Unsupported operator/)
+  assert.throws(() => u.whereNot('age', '>= 0 OR 1', 1), /Unsupported operator/)
+  assert.deepStrictEqual(u.where('age', '>=', 35).all().map((r) => r.name), ['bob'])
+  assert.deepStrictEqual(u.where('name', 'not  like', 'a%').all().map((r) => r.name), ['bob'])
+  assert.deepStrictEqual(u.whereFn('lower', ['name'], '=', 'ann').all().map((r) => r.name), ['ann'])
+})
+
+test('column arguments are validated', () => {
+  const db = users()
+  const u = db.table('users')
+  const bad = 'name) OR 1=1 OR LOWER(name'
+  assert.throws(() => db.table('users; DROP TABLE users'), /Invalid table name/)
+  assert.throws(() => u.where(bad, 'x'), /Invalid column name/)
+  assert.throws(() => u.orderBy('name; DROP TABLE users'), /Invalid column name/)
+  assert.throws(() => u.whereIlike(bad, 'x'), /Invalid column name/)
+  assert.throws(() => u.whereStartsWith(bad, 'x'), /Invalid column name/)
+  assert.throws(() => u.whereEndsWith(bad, 'x'), /Invalid column name/)
+  assert.throws(() => u.whereIn(bad, ['x']), /Invalid column name/)
+  assert.throws(() => u.whereInQuery(bad, u.select(['id'])), /Invalid column name/)
+  assert.throws(() => u.whereInRange(bad, 1, 2), /Invalid column name/)
+  assert.throws(() => u.whereTimestampBetween(bad, 1, 2), /Invalid column name/)
+  assert.throws(() => u.whereJsonContains(bad, '$', 'a'), /Invalid column name/)
+  assert.throws(() => u.select(['id', '(SELECT 1)']), /Invalid column name/)
+  assert.throws(() => u.groupBy(['name, 1']), /Invalid column name/)
+  assert.throws(() => u.sum('age) FROM users; --'), /Invalid column name/)
+  assert.strictEqual(db.table('users').count(), 2)
+
+  assert.deepStrictEqual(u.whereIlike('name', 'ANN').all().map((r) => r.id), [1])
+  assert.deepStrictEqual(u.whereInQuery('id', u.where('age', 40).select(['id'])).all().map((r) => r.name), ['bob'])
+  assert.deepStrictEqual(u.select(['users.name']).orderBy('users.id').all(), [{ name: 'ann' }, { name: 'bob' }])
+  assert.strictEqual(u.select(['*']).all().length, 2)
+})
+
+test('schema-qualified table names quote each part', () => {
+  const db = new Database(':memory:')
+  db.execute("ATTACH ':memory:' AS aux")
+  db.execute('CREATE TABLE aux.users (id INTEGER PRIMARY KEY, name TEXT)')
+  const u = db.table('aux.users')
+  u.insert({ name: 'ann', unknown: 1 }, { ignoreUnknown: true })
+  assert.deepStrictEqual(u.columns(), ['id', 'name'])
+  assert.match(u.schemaSql(), /^CREATE TABLE users/)
+  assert.deepStrictEqual(u.as('q').where('q.name', 'ann').all(), [{ id: 1, name: 'ann' }])
+  u.addColumn('age', 'INTEGER')
+  u.renameColumn('age', 'years')
+  assert.deepStrictEqual(u.columns(), ['id', 'name', 'years'])
+  u.createIndex(['name'])
+  assert.deepStrictEqual(db.query("SELECT name, tbl_name FROM aux.sqlite_master WHERE type = 'index'"), [
+    { name: 'idx_users_name', tbl_name: 'users' },
+  ])
+  db.execute('CREATE TABLE aux.posts (id INTEGER PRIMARY KEY, user_id INTEGER)')
+  db.table('aux.posts').insert({ user_id: 1 })
+  const withPosts = u.hasMany('posts', 'aux.posts', 'user_id').withJson('posts').all()
+  assert.deepStrictEqual(withPosts[0].posts, [{ id: 1, user_id: 1 }])
+})
+
+test('where(object) rejects arrays and objects and skips undefined', () => {
+  const u = users().table('users')
+  assert.throws(() => u.where({ tags: ['a'] }), /array, object or function/)
+  assert.throws(() => u.where({ tags: { a: 1 } }), /array, object or function/)
+  assert.deepStrictEqual(u.where({ name: 'ann', age: undefined }).all().map((r) => r.id), [1])
+  assert.deepStrictEqual(u.where({ tags: null }).all(), [])
+})
+
+test('countDistinct runs over groups and windows like count', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE s (id INTEGER PRIMARY KEY, g TEXT)')
+  db.table('s').insert([{ g: 'a' }, { g: 'a' }, { g: 'b' }, { g: 'c' }])
+  const s = db.table('s')
+  assert.strictEqual(s.countDistinct('g'), 3)
+  assert.strictEqual(s.groupBy(['g']).count(), 3)
+  assert.strictEqual(s.groupBy(['g']).countDistinct('g'), 3)
+  assert.strictEqual(s.orderBy('id').limit(2).countDistinct('g'), 1)
+  assert.strictEqual(s.orderBy('id').offset(2).countDistinct('g'), 2)
+})
+
+test('sum, avg, min and max aggregate the filtered rows', () => {
+  const t = users().table('users')
+  assert.strictEqual(t.sum('age'), 70)
+  assert.strictEqual(t.avg('age'), 35)
+  assert.strictEqual(t.min('age'), 30)
+  assert.strictEqual(t.max('users.age'), 40)
+  assert.strictEqual(t.where('age', '>', 30).sum('age'), 40)
+  const none = t.where('age', '>', 100)
+  assert.strictEqual(none.sum('age'), null)
+  assert.strictEqual(none.avg('age'), null)
+  assert.strictEqual(none.min('age'), null)
+  assert.strictEqual(none.max('age'), null)
+  for (const fn of ['sum', 'avg', 'min', 'max']) {
+    assert.throws(() => t[fn]('nope'), /no such column: nope/)
+    assert.throws(() => t[fn]('age) FROM users; --'), /Invalid column name/)
+  }
+})
+
+test('countDistinct counts distinct statuses in a filtered subset', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE orders (id INTEGER PRIMARY KEY, status TEXT, total INTEGER)')
+  db.table('orders').insert([
+    { status: 'paid', total: 10 },
+    { status: 'paid', total: 20 },
+    { status: 'shipped', total: 30 },
+    { status: 'refunded', total: 5 },
+  ])
+  const orders = db.table('orders')
+  assert.strictEqual(orders.where('total', '>=', 10).countDistinct('status'), 2)
+  assert.strictEqual(orders.where('total', '>=', 10).countDistinct('orders.status'), 2)
+  assert.throws(() => orders.countDistinct('nope'), /no such column: nope/)
+  assert.throws(() => orders.countDistinct('status) FROM orders; --'), /Invalid column name/)
+})
+
+test('bigint mode returns counts and integers as BigInt', () => {
+  const db = new Database(':memory:', { bigint: true })
+  db.execute('CREATE TABLE big (id INTEGER PRIMARY KEY, g TEXT, n REAL)')
+  const big = db.table('big')
+  // Past 2^53, where a JS number can no longer hold every integer
+  big.insert([{ id: 9007199254740993n, g: 'a', n: 1.5 }, { g: 'a' }, { g: 'b' }])
+  assert.strictEqual(big.count(), 3n)
+  assert.strictEqual(big.where('g', 'a').count(), 2n)
+  assert.strictEqual(big.countDistinct('g'), 2n)
+  assert.deepStrictEqual(db.query('SELECT id, n FROM big ORDER BY id LIMIT 1'), [{ id: 9007199254740993n, n: 1.5 }])
+  assert.deepStrictEqual(db.query('SELECT g FROM big WHERE id = ?', [9007199254740994n]), [{ g: 'a' }])
+  assert.throws(() => db.query('SELECT ?', [2n ** 64n]), /out of range/)
+  assert.strictEqual(new Database(':memory:').table('sqlite_master').count(), 0)
+})
+
+test('all() with a limit reads 50k rows into a preallocated result', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE n (id INTEGER PRIMARY KEY, v INTEGER)')
+  db.execute('WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 50000) INSERT INTO n (v) SELECT x * 2 FROM c')
+  const n = db.table('n')
+  let started = process.hrtime.bigint()
+  const unsized = n.orderBy('id').all()
+  const unsizedMs = Number(process.hrtime.bigint() - started) / 1e6
+  started = process.hrtime.bigint()
+  const sized = n.orderBy('id').limit(50000).all()
+  const sizedMs = Number(process.hrtime.bigint() - started) / 1e6
+  assert.strictEqual(sized.length, 50000)
+  assert.deepStrictEqual(sized[49999], { id: 50000, v: 100000 })
+  assert.deepStrictEqual(sized, unsized)
+  // The preallocation is capped, so a limit far past the row count costs nothing extra
+  assert.strictEqual(n.limit(1e12).all().length, 50000)
+  assert.strictEqual(n.limit(10).all().length, 10)
+  console.log(`all() over 50k rows: ${unsizedMs.toFixed(1)} ms without a limit, ${sizedMs.toFixed(1)} ms with one`)
+})
+
+test('latestPer returns the latest order per customer', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE o (id INTEGER PRIMARY KEY, customer TEXT, placed_at TEXT)')
+  db.table('o').insert([
+    { customer: 'a', placed_at: '2024-01-01' },
+    { customer: 'a', placed_at: '2024-03-01' },
+    { customer: 'b', placed_at: '2024-02-01' },
+  ])
+  assert.deepStrictEqual(db.table('o').latestPer('customer', 'placed_at'), [
+    { id: 2, customer: 'a', placed_at: '2024-03-01' },
+    { id: 3, customer: 'b', placed_at: '2024-02-01' },
+  ])
+})
+
+test('replace swaps a filtered subset and resets columns missing from data', () => {
+  const db = new Database(':memory:')
+  db.execute(`CREATE TABLE s (id INTEGER PRIMARY KEY, k TEXT, a TEXT, b TEXT DEFAULT 'dflt')`)
+  db.table('s').insert([
+    { k: 'x', a: '1', b: '2' },
+    { k: 'x', a: '3', b: '4' },
+    { k: 'y', a: '5', b: '6' },
+  ])
+  db.table('s').where('k', 'x').replace({ k: 'x', a: 'new' })
+  assert.deepStrictEqual(db.query('SELECT * FROM s ORDER BY id'), [
+    { id: 3, k: 'y', a: '5', b: '6' },
+    { id: 4, k: 'x', a: 'new', b: 'dflt' },
+  ])
+})
+
+test('array output matches the object output for the same query', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE s (id INTEGER PRIMARY KEY, k TEXT, r REAL, n)')
+  db.execute("INSERT INTO s (k, r, n) VALUES ('x', 1.5, NULL), ('y', 2, 3)")
+  const sql = 'SELECT * FROM s WHERE id > ?'
+  const { columns, rows } = db.queryRows(sql, [0])
+  assert.deepStrictEqual(columns, ['id', 'k', 'r', 'n'])
+  const asObjects = rows.map((row) => Object.fromEntries(columns.map((c, i) => [c, row[i]])))
+  assert.deepStrictEqual(asObjects, db.query(sql, [0]))
+  assert.deepStrictEqual(db.table('s').where('k', 'y').asArray(), { columns, rows: [[2, 'y', 2, 3]] })
+})
+
+test('chained conditions keep their order in the SQL', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, a INTEGER, b TEXT)')
+  db.table('t').insert([
+    { a: 1, b: 'x' },
+    { a: 2, b: 'x' },
+    { a: 3, b: 'y' },
+    { a: 4, b: 'x' },
+  ])
+  const q = db.table('t').orderBy('a', 'DESC').where('b', 'x').where('a', '>', 1).whereRaw('a < ?', [4])
+  assert.strictEqual(q.toSql(), 'SELECT * FROM t WHERE b = ? AND a > ? AND (a < ?) ORDER BY a DESC')
+  assert.deepStrictEqual(q.bindings(), ['x', 1, 4])
+  assert.deepStrictEqual(q.all().map((r) => r.id), [2])
+})
+
+test('updateRaw decrements a balance for a filtered account', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE acc (id INTEGER PRIMARY KEY, owner TEXT, balance INTEGER)')
+  db.table('acc').insert([
+    { owner: 'a', balance: 100 },
+    { owner: 'b', balance: 100 },
+  ])
+  db.table('acc').where('owner', 'a').updateRaw('balance = balance - ?', [30])
+  assert.deepStrictEqual(db.query('SELECT owner, balance FROM acc ORDER BY id'), [
+    { owner: 'a', balance: 70 },
+    { owner: 'b', balance: 100 },
+  ])
+})
+
+test('firstOrFail returns the row or throws with the query', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, name TEXT)')
+  db.table('u').insert({ name: 'al' })
+  assert.deepStrictEqual(db.table('u').where('name', 'al').firstOrFail().toObject(), { id: 1, name: 'al' })
+  assert.throws(
+    () => db.table('u').where('name', 'zz').where('id', '>', 3).firstOrFail(),
+    { message: "No row found for `SELECT * FROM u WHERE name = ? AND id > ?` with bindings ['zz', 3]" },
+  )
+})
+
+test('whereNot negates an IN condition and a group', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE o (id INTEGER PRIMARY KEY, status TEXT, n INTEGER)')
+  db.table('o').insert([
+    { status: 'a', n: 1 },
+    { status: 'b', n: 2 },
+    { status: 'c', n: 3 },
+    { status: 'a', n: 4 },
+  ])
+  const q = db.table('o').whereNot('status', 'IN', 'a,b')
+  assert.strictEqual(q.toSql(), 'SELECT * FROM o WHERE NOT (status IN (?, ?))')
+  assert.deepStrictEqual(q.all().map((r) => r.id), [3])
+  const g = db
+    .table('o')
+    .where('n', '>', 0)
+    .whereNot((q) => q.where('status', 'a').where('n', '>', 2))
+  assert.strictEqual(g.toSql(), 'SELECT * FROM o WHERE n > ? AND NOT (status = ? AND n > ?)')
+  assert.deepStrictEqual(g.all().map((r) => r.id), [1, 2, 3])
+})
+
+test('has and doesntHave filter parents by child existence', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT); CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER)')
+  db.table('users').insert([{ name: 'a' }, { name: 'b' }, { name: 'c' }])
+  db.table('posts').insert([{ user_id: 1 }, { user_id: 3 }])
+  const users = db.table('users').hasMany('posts', 'posts', 'user_id')
+  assert.deepStrictEqual(users.has('posts').all().map((r) => r.name), ['a', 'c'])
+  assert.deepStrictEqual(users.doesntHave('posts').all().map((r) => r.name), ['b'])
+  const posts = db.table('posts').belongsTo('author', 'users', 'user_id')
+  assert.strictEqual(posts.where('id', '>', 0).has('author').count(), 2)
+  assert.throws(() => users.has('nope'), /Unknown relation nope on table users/)
+})
+
+test('update and destroy can return the affected rows', () => {
+  const t = users().table('users')
+  assert.deepStrictEqual(
+    t.where('age', '>', 0).update({ age: 50 }, { returning: true }).map((r) => [r.id, r.age]),
+    [
+      [1, 50],
+      [2, 50],
+    ],
+  )
+  assert.deepStrictEqual(t.where('age', 50).update({ age: 51 }, { returning: true, limit: 1 }).map((r) => r.id), [1])
+  assert.strictEqual(t.where('age', 50).update({ age: 52 }), 1)
+  assert.deepStrictEqual(t.where('name', 'ann').destroy({ returning: true }).map((r) => r.name), ['ann'])
+  assert.strictEqual(t.count(), 1)
+})
+
+test('booleans in where match rows stored as 1 and 0', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, name TEXT, active INT)')
+  const t = db.table('u')
+  t.insert([
+    { name: 'a', active: true },
+    { name: 'b', active: 0 },
+    { name: 'c', active: 1 },
+  ])
+  assert.deepStrictEqual(t.where('active', true).all().map((r) => r.id), [1, 3])
+  assert.deepStrictEqual(t.where('active', '=', false).all().map((r) => r.id), [2])
+  assert.deepStrictEqual(t.whereNot('active', true).all().map((r) => r.id), [2])
+  assert.deepStrictEqual(t.where('active', true).bindings(), [1])
+})
+
+test('whereIlike matches regardless of case on a BINARY column', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE w (id INTEGER PRIMARY KEY, word TEXT COLLATE BINARY)')
+  db.table('w').insert([{ word: 'foo' }, { word: 'bar' }])
+  assert.deepStrictEqual(db.table('w').where('word', 'GLOB', 'FOO').all(), [])
+  assert.deepStrictEqual(db.table('w').whereIlike('word', 'FOO').all(), [{ id: 1, word: 'foo' }])
+  assert.deepStrictEqual(db.table('w').whereIlike('word', 'F%').all().map((r) => r.id), [1])
+})
+
+test('withCte lets the builder select from a CTE', () => {
+  const db = users()
+  db.table('users').insert({ name: 'cat', age: 50 })
+  const older = db.withCte('older', 'SELECT id, name, age FROM users WHERE age >= 40')
+  assert.deepStrictEqual(older.table('older').orderBy('age', 'desc').all(), [
+    { id: 3, name: 'cat', age: 50 },
+    { id: 2, name: 'bob', age: 40 },
+  ])
+  assert.strictEqual(older.table('older').where('name', 'bob').count(), 1)
+  assert.match(older.table('older').where('age', '>', 40).toSql(), /^WITH "older" AS \(SELECT id, name, age FROM users WHERE age >= 40\) SELECT/)
+})
+
+test('whereInQuery filters users by a filtered orders subquery', () => {
+  const db = users()
+  db.execute('CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER, total INTEGER)')
+  db.table('orders').insert([
+    { user_id: 1, total: 5 },
+    { user_id: 2, total: 500 },
+  ])
+  const big = db.table('orders').where('total', '>', 100).select(['user_id'])
+  const q = db.table('users').whereInQuery('id', big)
+  assert.strictEqual(q.toSql(), 'SELECT * FROM users WHERE id IN (SELECT user_id FROM orders WHERE total > ?)')
+  assert.deepStrictEqual(q.all().map((r) => r.name), ['bob'])
+})
+
+test('whereFn filters by length(name) > 5', () => {
+  const db = users()
+  db.table('users').insert({ name: 'charlotte', age: 20 })
+  const q = db.table('users').whereFn('length', ['name'], '>', 5)
+  assert.strictEqual(q.toSql(), 'SELECT * FROM users WHERE length("name") > ?')
+  assert.deepStrictEqual(q.all().map((r) => r.name), ['charlotte'])
+})
+
+test('withJson loads users with a JSON array of their posts in one query', () => {
+  const db = users()
+  db.execute('CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER, title TEXT)')
+  db.table('posts').insert([
+    { user_id: 1, title: 'p1' },
+    { user_id: 1, title: 'p2' },
+  ])
+  const q = db.table('users').hasMany('posts', 'posts', 'user_id').withJson('posts')
+  assert.match(q.toSql(), /json_group_array\(json_object\(/)
+  assert.deepStrictEqual(
+    q.all().map((u) => [u.name, u.posts.map((p) => p.title)]),
+    [
+      ['ann', ['p1', 'p2']],
+      ['bob', []],
+    ],
+  )
+})
+
+test('latest returns the most recent row first', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE posts (id INTEGER PRIMARY KEY, created_at TEXT, published_at TEXT)')
+  db.table('posts').insert([
+    { created_at: '2024-02-01', published_at: '2024-03-01' },
+    { created_at: '2024-03-01', published_at: '2024-01-01' },
+    { created_at: '2024-01-01', published_at: '2024-02-01' },
+  ])
+  const posts = db.table('posts')
+  assert.deepStrictEqual(posts.latest().all().map((r) => r.id), [2, 1, 3])
+  assert.strictEqual(posts.latest().first().id, 2)
+  assert.deepStrictEqual(posts.oldest('published_at').all().map((r) => r.id), [2, 3, 1])
+  assert.strictEqual(posts.where('id', '>', 1).latest().toSql(), 'SELECT * FROM posts WHERE id > ? ORDER BY created_at DESC')
+})
+
+test('within finds R*Tree boxes overlapping an area', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE VIRTUAL TABLE boxes USING rtree(id, minX, maxX, minY, maxY)')
+  db.table('boxes').insert([
+    { id: 1, minX: 0, maxX: 10, minY: 0, maxY: 10 },
+    { id: 2, minX: 20, maxX: 30, minY: 20, maxY: 30 },
+    { id: 3, minX: 50, maxX: 60, minY: 50, maxY: 60 },
+  ])
+  assert.deepStrictEqual(db.table('boxes').within(8, 22, 8, 22).all().map((r) => r.id), [1, 2])
+  assert.deepStrictEqual(db.table('boxes').within(11, 19, 11, 19).all(), [])
+  db.execute('CREATE TABLE plain (id INTEGER PRIMARY KEY)')
+  assert.throws(() => db.table('plain').within(0, 1, 0, 1), /plain is not an R\*Tree virtual table/)
+})
+
+test('raw fragment bindings follow clause order', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, a INTEGER, b INTEGER, g TEXT)')
+  for (const [a, b, g] of [[1, 3, 'x'], [1, 4, 'x'], [1, 5, 'x'], [1, 9, 'y'], [2, 9, 'y'], [1, 1, 'z']]) {
+    db.table('t').insert({ a, b, g })
+  }
+  const q = db.table('t').where('a', '=', 1).whereRaw('b > ?', [2]).havingRaw('count(*) > ?', [3])
+  assert.deepStrictEqual(q.bindings(), [1, 2, 3])
+  // Chained out of order, the bindings still follow the clauses in the SQL
+  const grouped = db
+    .table('t')
+    .havingRaw('count(*) > ?', [1])
+    .selectRaw('count(*) * ? AS n', [10])
+    .where('a', 1)
+    .whereRaw('b > ?', [2])
+    .groupBy(['g'])
+    .orderByRaw('n DESC, g = ?', ['y'])
+    .select(['g'])
+  assert.strictEqual(
+    grouped.toSql(),
+    'SELECT g, count(*) * ? AS n FROM t WHERE a = ? AND (b > ?) GROUP BY g HAVING (count(*) > ?) ORDER BY n DESC, g = ?',
+  )
+  assert.deepStrictEqual(grouped.bindings(), [10, 1, 2, 1, 'y'])
+  assert.deepStrictEqual(grouped.all(), [{ g: 'x', n: 30 }])
+})
+
+test('as aliases a table for a self-join', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE employees (id INTEGER PRIMARY KEY, name TEXT, manager_id INTEGER)')
+  db.table('employees').insert([{ name: 'Ann' }, { name: 'Bob', manager_id: 1 }, { name: 'Cy', manager_id: 2 }])
+  const reports = db
+    .table('employees')
+    .as('e')
+    .whereRaw('EXISTS (SELECT 1 FROM employees AS manager WHERE manager.id = e.manager_id AND manager.name = ?)', ['Ann'])
+  assert.match(reports.toSql(), /^SELECT \* FROM "employees" AS "e" WHERE/)
+  assert.deepStrictEqual(reports.all().map((r) => r.name), ['Bob'])
+  const managers = db
+    .table('employees')
+    .as('manager')
+    .where('manager.id', '<', 3)
+    .selectRaw('(SELECT count(*) FROM employees WHERE employees.manager_id = manager.id) AS reports')
+  assert.deepStrictEqual(managers.all().map((r) => [r.name, r.reports]), [
+    ['Ann', 1],
+    ['Bob', 1],
+  ])
+  db.table('employees').as('x').where('x.name', 'Cy').update({ name: 'Cyd' })
+  assert.strictEqual(db.table('employees').as('x').where('x.name', 'Cyd').destroy(), 1)
+})
+
+test('whereJsonContains matches JSON array members', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE posts (id INTEGER PRIMARY KEY, tags TEXT, meta TEXT)')
+  db.execute(
+    `INSERT INTO posts (tags, meta) VALUES ('["rust","sqlite"]', '{"langs":["en"]}'), ('["js"]', '{"langs":["de","en"]}'), (NULL, '{}')`,
+  )
+  const ids = (q) => q.all().map((r) => r.id)
+  assert.deepStrictEqual(ids(db.table('posts').whereJsonContains('tags', '$', 'rust')), [1])
+  assert.deepStrictEqual(ids(db.table('posts').whereJsonContains('meta', '$.langs', 'en')), [1, 2])
+  assert.deepStrictEqual(ids(db.table('posts').whereJsonContains('meta', '$.langs', 'fr')), [])
+})
+
+test('escapeLike makes wildcards match literally', () => {
+  const db = new Database(':memory:')
+  assert.strictEqual(Database.escapeLike('50%_a\\b'), '50\\%\\_a\\\\b')
+  db.execute('CREATE TABLE d (id INTEGER PRIMARY KEY, name TEXT)')
+  for (const name of ['50% off', '500 off', 'a_b', 'axb']) db.table('d').insert({ name })
+  const names = (q) => q.all().map((r) => r.name)
+  assert.deepStrictEqual(names(db.table('d').whereIlike('name', `${Database.escapeLike('50%')}%`)), ['50% off'])
+  assert.deepStrictEqual(names(db.table('d').whereIlike('name', Database.escapeLike('A_B'))), ['a_b'])
+  assert.deepStrictEqual(names(db.table('d').whereIlike('name', '50%')), ['50% off', '500 off'])
+})
+
+test('whereInRange excludes the end of the range', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE ev (id INTEGER PRIMARY KEY, n INTEGER, at TEXT)')
+  db.table('ev').insert([
+    { n: 1, at: '2024-01-01T00:00:00.000Z' },
+    { n: 5, at: '2024-01-31T23:59:59.000Z' },
+    { n: 10, at: '2024-02-01T00:00:00.000Z' },
+  ])
+  const ns = (q) => q.all().map((r) => r.n)
+  assert.deepStrictEqual(ns(db.table('ev').whereInRange('n', 1, 10)), [1, 5])
+  assert.deepStrictEqual(ns(db.table('ev').whereInRange('at', new Date('2024-01-01'), new Date('2024-02-01'))), [1, 5])
+  assert.throws(() => db.table('ev').whereInRange('n', null, 3), /start and an end/)
+})
+
+test('where accepts an object of equality conditions', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, status TEXT, role TEXT, team TEXT)')
+  db.table('users').insert([
+    { status: 'active', role: 'admin', team: 'a' },
+    { status: 'active', role: 'user' },
+    { status: 'banned', role: 'admin', team: 'b' },
+  ])
+  const ids = (q) => q.all().map((r) => r.id)
+  assert.deepStrictEqual(ids(db.table('users').where({ status: 'active', role: 'admin' })), [1])
+  assert.deepStrictEqual(ids(db.table('users').where({ team: null })), [2])
+  assert.deepStrictEqual(ids(db.table('users').where('role', 'admin').where({ status: 'banned' })), [3])
+  assert.throws(() => db.table('users').where('id'), /Invalid arguments for where/)
+})
+
+test('whereTimestampBetween compares unix seconds inclusively', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE ev (id INTEGER PRIMARY KEY, at INTEGER)')
+  const secs = (d) => Math.floor(new Date(d).getTime() / 1000)
+  for (const d of ['2024-01-01T00:00:00Z', '2024-01-15T12:00:00Z', '2024-02-01T00:00:00Z', '2024-02-01T00:00:01Z']) {
+    db.table('ev').insert({ at: secs(d) })
+  }
+  const ids = db
+    .table('ev')
+    .whereTimestampBetween('at', new Date('2024-01-01T00:00:00Z'), new Date('2024-02-01T00:00:00.999Z'))
+    .all()
+    .map((r) => r.id)
+  assert.deepStrictEqual(ids, [1, 2, 3])
+  assert.throws(() => db.table('ev').whereTimestampBetween('at', 5, new Date()), /Expected a Date/)
+  assert.throws(() => db.table('ev').whereTimestampBetween('at', new Date('x'), new Date()), /Invalid Date/)
+})
+
+test('cloneBuilder branches without touching the original', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE o (id INTEGER PRIMARY KEY, status TEXT, total INTEGER)')
+  db.table('o').insert([{ status: 'paid', total: 5 }, { status: 'paid', total: 50 }, { status: 'open', total: 7 }])
+  const ids = (q) => q.all().map((r) => r.id)
+  const base = db.table('o').where('total', '>', 1)
+  const paid = base.cloneBuilder().where('status', 'paid')
+  const open = base.cloneBuilder().where('status', 'open')
+  assert.deepStrictEqual(ids(paid), [1, 2])
+  assert.deepStrictEqual(ids(open), [3])
+  assert.deepStrictEqual(ids(base), [1, 2, 3])
+  assert.strictEqual(base.toSql(), 'SELECT * FROM o WHERE total > ?')
+})
+
+test('whereIn matches NULL members with IS NULL', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, team TEXT)')
+  db.table('t').insert([{ team: 'a' }, { team: 'b' }])
+  // insert() skips a row with only NULL values, so the NULL row goes in directly
+  db.execute('INSERT INTO t DEFAULT VALUES')
+  db.table('t').insert({ team: 'c' })
+  const ids = (q) => q.all().map((r) => r.id)
+  assert.deepStrictEqual(ids(db.table('t').whereIn('team', ['a', null])), [1, 3])
+  assert.deepStrictEqual(ids(db.table('t').whereIn('team', [null])), [3])
+  assert.deepStrictEqual(ids(db.table('t').whereIn('team', [])), [])
+  assert.deepStrictEqual(ids(db.table('t').whereNot((q) => q.whereIn('team', ['a', null]))), [2, 4])
+})
+
+test('whereStartsWith and whereEndsWith match wildcards literally', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE c (id INTEGER PRIMARY KEY, code TEXT)')
+  db.table('c').insert(['50%off', '500', 'x_50%', 'off50%', 'Sale_1', 'sale11'].map((code) => ({ code })))
+  const codes = (q) => q.all().map((r) => r.code)
+  assert.deepStrictEqual(codes(db.table('c').whereStartsWith('code', '50%')), ['50%off'])
+  assert.deepStrictEqual(codes(db.table('c').whereStartsWith('code', '50')), ['50%off', '500'])
+  assert.deepStrictEqual(codes(db.table('c').whereEndsWith('code', '_50%')), ['x_50%'])
+  assert.deepStrictEqual(codes(db.table('c').whereStartsWith('code', 'sale_')), ['Sale_1'])
+})
+
+test('limit and offset page through the builder', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, age INTEGER)')
+  db.table('u').insert(Array.from({ length: 50 }, (_, i) => ({ age: i + 1 })))
+  const ids = (rows) => rows.map((r) => r.id)
+  const q = db.table('u').where('age', '>', 18).limit(10).offset(20)
+  assert.match(q.toSql(), /LIMIT \? OFFSET \?$/)
+  assert.deepStrictEqual(ids(q.all()), [39, 40, 41, 42, 43, 44, 45, 46, 47, 48])
+  assert.deepStrictEqual(ids(db.table('u').offset(47).all()), [48, 49, 50])
+  assert.strictEqual(db.table('u').limit(5).last().id, 5)
+  assert.throws(() => db.table('u').limit(0), /positive integer/)
+  assert.throws(() => db.table('u').offset(-1), /negative/)
+})
+
+test('orderBySafe only orders by allowed columns', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE p (id INTEGER PRIMARY KEY, title TEXT, secret TEXT)')
+  db.table('p').insert([{ title: 'b' }, { title: 'c' }, { title: 'a' }])
+  const allowed = ['id', 'title']
+  const titles = (q) => q.all().map((r) => r.title)
+  assert.deepStrictEqual(titles(db.table('p').orderBySafe('title', 'desc', allowed)), ['c', 'b', 'a'])
+  assert.deepStrictEqual(titles(db.table('p').where('id', '>', 1).orderBySafe('title', undefined, allowed)), ['a', 'c'])
+  assert.throws(() => db.table('p').orderBySafe('secret', 'asc', allowed), /not allowed/)
+  assert.throws(() => db.table('p').orderBySafe('(SELECT 1)', 'asc', allowed), /not allowed/)
+  assert.throws(() => db.table('p').orderBySafe('title', 'asc; DROP TABLE p', allowed), /direction/)
+})
+
+test('count counts the filtered rows', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, age INTEGER, team TEXT)')
+  db.table('u').insert(Array.from({ length: 30 }, (_, i) => ({ age: i + 1, team: `t${(i + 1) % 3}` })))
+  assert.strictEqual(db.table('u').count(), 30)
+  assert.strictEqual(db.table('u').where('age', '>', 18).orderBy('age', 'desc').count(), 12)
+  assert.strictEqual(db.table('u').groupBy(['team']).selectRaw('team').count(), 3)
+  assert.strictEqual(db.table('u').limit(7).count(), 7)
+  assert.strictEqual(db.table('u').where('age', '>', 100).count(), 0)
+})
+
+test('defaultOperator sets the operator of two-argument where', () => {
+  const db = new Database(':memory:', { defaultOperator: 'is' })
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, team TEXT)')
+  db.table('t').insert([{ team: 'a' }, { team: 'b' }])
+  db.execute('INSERT INTO t DEFAULT VALUES')
+  assert.match(db.table('t').where('team', 'a').toSql(), /team IS \?/)
+  assert.match(db.table('t').where({ team: 'a' }).toSql(), /team IS \?/)
+  assert.match(db.table('t').where('id', '>', 1).toSql(), /id > \?/)
+  assert.deepStrictEqual(db.table('t').whereNot('team', 'a').all().map((r) => r.id), [2, 3])
+  assert.match(new Database(':memory:').table('t').where('team', 'a').toSql(), /team = \?/)
+  assert.throws(() => new Database(':memory:', { defaultOperator: 'like' }), /Unknown default operator/)
+})
+
+test('paginate links the neighbouring pages', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, age INTEGER)')
+  db.table('u').insert(Array.from({ length: 25 }, (_, i) => ({ age: i + 1 })))
+  const first = db.table('u').paginate(1, 10, { links: true })
+  assert.strictEqual(first.total, 25)
+  assert.strictEqual(first.lastPage, 3)
+  assert.strictEqual(first.prevPage, null)
+  assert.strictEqual(first.nextPage, 2)
+  const last = db.table('u').paginate(3, 10, { links: true })
+  assert.deepStrictEqual(last.data.map((r) => r.id), [21, 22, 23, 24, 25])
+  assert.strictEqual(last.prevPage, 2)
+  assert.strictEqual(last.nextPage, null)
+  const empty = db.table('u').where('age', '>', 100).paginate(1, 10, { links: true })
+  assert.strictEqual(empty.prevPage, null)
+  assert.strictEqual(empty.nextPage, null)
+  assert.ok(!('nextPage' in db.table('u').paginate(1, 10)))
+})
+
+test('enumColumn constrains values and whereEnum rejects unknown ones', () => {
+  const db = new Database(':memory:')
+  const def = Database.enumColumn('status', ['draft', 'published', "it's"])
+  assert.strictEqual(def, `"status" TEXT CHECK ("status" IN ('draft', 'published', 'it''s'))`)
+  db.execute(`CREATE TABLE posts (id INTEGER PRIMARY KEY, ${def}, title TEXT)`)
+  db.table('posts').insert([{ status: 'draft', title: 'a' }, { status: 'published', title: 'b' }])
+  assert.throws(() => db.table('posts').insert({ status: 'publsihed', title: 'x' }), /CHECK constraint failed/)
+  assert.deepStrictEqual(db.table('posts').whereEnum('status', 'published').all().map((r) => r.title), ['b'])
+  assert.throws(() => db.table('posts').whereEnum('status', 'publsihed'), /not a valid value for status/)
+  assert.throws(() => db.table('posts').whereEnum('title', 'a'), /not an enum column/)
+  assert.throws(() => Database.enumColumn('x', []), /at least one value/)
+})
+
+test('whereJsonMatch matches several JSON paths at once', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE docs (id INTEGER PRIMARY KEY, body TEXT)')
+  db.table('docs').insert(
+    [
+      { a: 1, b: { c: 'x' }, ok: true },
+      { a: 1, b: { c: 'y' }, ok: false },
+      { a: 2, b: { c: 'x' }, n: null },
+    ].map((body) => ({ body: JSON.stringify(body) })),
+  )
+  const ids = (q) => q.all().map((r) => r.id)
+  assert.deepStrictEqual(ids(db.table('docs').whereJsonMatch('body', { '$.a': 1, '$.b.c': 'x' })), [1])
+  assert.deepStrictEqual(ids(db.table('docs').whereJsonMatch('body', { a: 1 })), [1, 2])
+  assert.deepStrictEqual(ids(db.table('docs').whereJsonMatch('body', { ok: false })), [2])
+  assert.deepStrictEqual(ids(db.table('docs').whereJsonMatch('body', { n: null, 'b.c': 'x' })), [1, 3])
+  assert.throws(() => db.table('docs').whereJsonMatch('body) OR (1', { a: 1 }), /Invalid column name/)
+})
+
+test('an AbortSignal cancels a builder allAsync', async () => {
+  const db = new Database(':memory:')
+  db.execute(
+    'CREATE VIEW endless AS WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) AS n FROM c',
+  )
+  const ac = new AbortController()
+  const running = db.table('endless').allAsync({ signal: ac.signal })
+  setTimeout(() => ac.abort(), 50)
+  await assert.rejects(running, (e) => e.code === 'Cancelled' && /aborted/.test(e.message))
+  assert.throws(() => db.table('endless').allAsync({ signal: AbortSignal.abort() }), (e) => e.code === 'Cancelled')
+  db.execute('DROP VIEW endless')
+})
@@ -0,0 +1,473 @@
+const test = require('node:test')
+const assert = require('node:assert')
+const { Database } = require('../index.js')
+
+function seed() {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT, g TEXT)')
+  return db
+}
+
+test('destroy with a limit only deletes the first matching rows', () => {
+  const db = seed()
+  db.table('t').insert([{ g: 'a' }, { g: 'a' }, { g: 'a' }, { g: 'a' }, { g: 'b' }])
+  db.table('t').where('g', 'a').orderBy('id').destroy({ limit: 2 })
+  assert.deepStrictEqual(
+    db.query('SELECT id FROM t ORDER BY id').map((r) => r.id),
+    [3, 4, 5],
+  )
+  assert.throws(() => db.table('t').where('g', 'a').destroy({ limit: 0 }))
+})
+
+test('update keeps quotes in values intact', () => {
+  const db = seed()
+  db.table('t').insert([{ name: 'a' }, { name: 'b' }])
+  db.table('t').where('id', 1).update({ name: "O'Brien" })
+  assert.strictEqual(db.table('t').find(1).name, "O'Brien")
+  db.table('t').update(2, { name: "x'); DROP TABLE t; --" })
+  assert.strictEqual(db.table('t').find(2).name, "x'); DROP TABLE t; --")
+  assert.strictEqual(db.table('t').where('name', "O'Brien").count(), 1)
+})
+
+test('update binds values the same way insert does', () => {
+  const db = new Database(':memory:', { dateFormat: 'unix' })
+  db.execute('CREATE TABLE v (id INTEGER PRIMARY KEY, tags TEXT, at INTEGER, score REAL)')
+  const v = db.table('v')
+  v.insert({ tags: 5, at: new Date(1000), score: 1.5 })
+  v.insert({ tags: 'x' })
+  v.where('id', 2).update({ tags: 5, at: new Date(1000), score: 1.5 })
+  assert.deepStrictEqual(v.find(2).toObject(), { ...v.find(1).toObject(), id: 2 })
+  assert.strictEqual(db.getValue('SELECT tags FROM v WHERE id = 2'), '5')
+  assert.throws(() => v.where('id', 2).update({ score: NaN }), /non-finite/)
+  assert.throws(() => v.where('id', 2).update({ tags: () => 1 }), /Unsupported value type/)
+})
+
+test('copyTo validates the destination table name', () => {
+  const db = seed()
+  db.execute('CREATE TABLE t_archive (id INTEGER PRIMARY KEY, name TEXT, g TEXT)')
+  db.table('t').insert([{ g: 'a' }, { g: 'b' }])
+  assert.throws(() => db.table('t').copyTo('t_archive SELECT 1, 2, 3; --'), /Invalid table name/)
+  assert.strictEqual(db.table('t').copyTo('main.t_archive', db.table('t').where('g', 'b')), 1)
+  assert.deepStrictEqual(db.query('SELECT id, g FROM t_archive'), [{ id: 2, g: 'b' }])
+})
+
+test('insert skips rows with nothing to insert', () => {
+  const db = seed()
+  const t = db.table('t')
+  assert.strictEqual(t.insert({}), 0)
+  assert.deepStrictEqual(t.insert([{ name: null, g: undefined }, { g: 'a' }]), [0, 1])
+  assert.deepStrictEqual(t.insert([{ nope: 1 }], { ignoreUnknown: true }), [0])
+  assert.strictEqual(t.count(), 1)
+})
+
+test('getBy fetches a user by email', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email TEXT)')
+  const users = db.table('users')
+  users.insert([{ name: 'ann', email: 'ann@example.com' }, { name: 'bob', email: 'bob@example.com' }])
+  assert.strictEqual(users.getBy('email', 'bob@example.com').name, 'bob')
+  assert.strictEqual(users.getBy('email', 'nobody@example.com'), null)
+  assert.throws(() => users.getBy('nope', 'nope'), /no such column: nope/)
+})
+
+test('orIgnore reports only rows skipped by a constraint as ignored', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE)')
+  const users = db.table('users')
+  users.insert({ email: 'a@example.com' })
+  const res = users.insert([{ email: 'a@example.com' }, {}, { email: 'b@example.com' }], {
+    orIgnore: true,
+    result: true,
+  })
+  assert.strictEqual(res.changes, 1)
+  assert.strictEqual(res.ignored, 1)
+})
+
+test('a 10 MB blob and string round-trip without runaway memory', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE files (id INTEGER PRIMARY KEY, data BLOB, body TEXT)')
+  const size = 10 * 1024 * 1024
+  const data = Buffer.alloc(size, 7)
+  data[size - 1] = 9
+  // Built from a buffer so V8 holds it flat rather than flattening it during the insert
+  const body = Buffer.alloc(size, 'x').toString('latin1')
+  const before = process.memoryUsage().rss
+  const id = db.table('files').insert({ data, body })
+  const grown = process.memoryUsage().rss - before
+  // Compared in SQL by binding the same values again, rather than reading 10 MB back into JS
+  assert.deepStrictEqual(
+    db.query('SELECT id, length(data) AS bytes, data = ? AS same_data, body = ? AS same_body FROM files', [data, body]),
+    [{ id, bytes: size, same_data: 1, same_body: 1 }],
+  )
+  assert.strictEqual(db.table('files').typeOf(id, 'data'), 'blob')
+  // SQLite keeps its own copy of each value (20 MB) and the text is converted to UTF-8 once
+  // (10 MB); an extra copy of each value on the way in would be another 20 MB on top
+  assert.ok(grown < 48 * 1024 * 1024, `rss grew by ${Math.round(grown / 1048576)} MB`)
+})
+
+test('insert type hints keep a leading-zero string as TEXT', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, zip, n)')
+  db.table('t').insert({ zip: '01234', n: '42' }, { types: { zip: 'text', n: 'integer' } })
+  db.table('t').insert({ zip: 1234 }, { types: { zip: 'text' } })
+  assert.deepStrictEqual(db.query('SELECT zip, typeof(zip) AS tz, n, typeof(n) AS tn FROM t ORDER BY id'), [
+    { zip: '01234', tz: 'text', n: 42, tn: 'integer' },
+    { zip: '1234', tz: 'text', n: undefined, tn: 'null' },
+  ])
+  assert.throws(() => db.table('t').insert({ zip: 1 }, { types: { zip: 'bogus' } }), /Unknown type hint/)
+})
+
+test('sync adds, changes and drops rows to match the given set', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE c (code TEXT PRIMARY KEY, name TEXT)')
+  db.table('c').insert([
+    { code: 'a', name: 'A' },
+    { code: 'b', name: 'B' },
+    { code: 'c', name: 'C' },
+  ])
+  const res = db.table('c').sync('code', [
+    { code: 'a', name: 'A' },
+    { code: 'b', name: 'Bee' },
+    { code: 'd', name: 'D' },
+  ])
+  assert.deepStrictEqual(res, { inserted: 1, updated: 1, deleted: 1 })
+  assert.deepStrictEqual(db.query('SELECT * FROM c ORDER BY code'), [
+    { code: 'a', name: 'A' },
+    { code: 'b', name: 'Bee' },
+    { code: 'd', name: 'D' },
+  ])
+})
+
+test('insert and update quote keyword column names', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE k (id INTEGER PRIMARY KEY, "order" INTEGER, "group" TEXT)')
+  const k = db.table('k')
+  k.insert({ order: 1, group: 'x' })
+  k.update(1, { order: 2, group: 'y' })
+  assert.deepStrictEqual(k.find(1).toObject(), { id: 1, order: 2, group: 'y' })
+  assert.deepStrictEqual(k.sync('id', [{ id: 1, order: 3, group: 'y' }]), { inserted: 0, updated: 1, deleted: 0 })
+  assert.deepStrictEqual(db.query('SELECT "order", "group" FROM k'), [{ order: 3, group: 'y' }])
+})
+
+test('update clears a column with null and skips undefined', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE k (id INTEGER PRIMARY KEY, name TEXT, deleted_at TEXT)')
+  const k = db.table('k')
+  k.insert({ name: 'a', deleted_at: '2024-01-01' })
+  k.update(1, { deleted_at: null, name: undefined })
+  assert.deepStrictEqual(db.query('SELECT name, typeof(deleted_at) AS t FROM k'), [{ name: 'a', t: 'null' }])
+})
+
+test('copyTo archives rows older than a date', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE e (id INTEGER PRIMARY KEY, at TEXT); CREATE TABLE e_archive (id INTEGER PRIMARY KEY, at TEXT)')
+  db.table('e').insert([{ at: '2020-01-01' }, { at: '2021-01-01' }, { at: '2025-01-01' }])
+  const old = db.table('e').where('at', '<', '2022-01-01')
+  assert.strictEqual(db.table('e').copyTo('e_archive', old), 2)
+  assert.deepStrictEqual(db.query('SELECT * FROM e_archive'), [
+    { id: 1, at: '2020-01-01' },
+    { id: 2, at: '2021-01-01' },
+  ])
+  assert.throws(() => db.table('e_archive').copyTo('e', old), /Filter targets e but copy source is e_archive/)
+})
+
+test('only declared JSON columns accept object values', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE d (id INTEGER PRIMARY KEY, meta TEXT, other TEXT)')
+  const d = db.table('d')
+  d.insert({ meta: { a: 1 } }, { jsonColumns: ['meta'] })
+  assert.throws(
+    () => d.insert({ meta: { a: 1 }, other: { b: 2 } }, { jsonColumns: ['meta'] }),
+    /Column other received an object but is not declared as a JSON column/,
+  )
+  assert.deepStrictEqual(db.query('SELECT meta FROM d'), [{ meta: '{"a":1}' }])
+})
+
+test('insert returns one sequential id per row', () => {
+  const db = seed()
+  assert.strictEqual(db.table('t').insert({ g: 'a' }), 1)
+  assert.deepStrictEqual(db.table('t').insert([{ g: 'b' }, { g: 'c' }, { g: 'd' }]), [2, 3, 4])
+})
+
+test('renameTable and renameColumn keep the data', () => {
+  const db = seed()
+  db.table('t').insert([{ name: 'a', g: 'x' }, { name: 'b', g: 'y' }])
+  db.renameTable('t', 'things')
+  assert.deepStrictEqual(db.tables(), ['things'])
+  db.table('things').renameColumn('g', 'grp')
+  assert.deepStrictEqual(db.table('things').columns(), ['id', 'name', 'grp'])
+  assert.deepStrictEqual(db.query('SELECT name, grp FROM things ORDER BY id'), [
+    { name: 'a', grp: 'x' },
+    { name: 'b', grp: 'y' },
+  ])
+})
+
+test('addColumn gives existing rows NULL and dropColumn removes it', () => {
+  const db = seed()
+  db.table('t').insert([{ name: 'a' }, { name: 'b' }])
+  db.table('t').addColumn('nickname', 'TEXT')
+  assert.deepStrictEqual(db.query('SELECT typeof(nickname) AS t FROM t'), [{ t: 'null' }, { t: 'null' }])
+  assert.throws(() => db.table('t').addColumn('x', 'TEXT); DROP TABLE t; --'), /type/i)
+  db.table('t').dropColumn('nickname')
+  assert.deepStrictEqual(db.table('t').columns(), ['id', 'name', 'g'])
+})
+
+test('insert can return a result object', () => {
+  const db = seed()
+  db.table('t').insert({ g: 'a' })
+  assert.deepStrictEqual(db.table('t').insert([{ g: 'b' }, { g: 'c' }], { result: true }), {
+    changes: 2,
+    lastInsertRowid: 3,
+  })
+  assert.strictEqual(db.table('t').insert({ g: 'd' }), 4)
+})
+
+test('a partial index is used by queries matching its condition', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, email TEXT, active INTEGER)')
+  db.table('u').createIndex(['email'], { where: 'active = 1', name: 'idx_active_email' })
+  const plan = (sql) => db.query(`EXPLAIN QUERY PLAN ${sql}`, ['a']).map((r) => r.detail).join('\n')
+  assert.match(plan('SELECT * FROM u WHERE email = ? AND active = 1'), /USING (COVERING )?INDEX idx_active_email/)
+  assert.doesNotMatch(plan('SELECT * FROM u WHERE email = ?'), /idx_active_email/)
+  db.table('u').createIndex(['active'], { where: db.table('u').where('email', 'LIKE', '%@x.com') })
+  assert.deepStrictEqual(db.query("SELECT sql FROM sqlite_master WHERE name = 'idx_u_active'"), [
+    { sql: `CREATE INDEX "idx_u_active" ON "u" ("active") WHERE email LIKE '%@x.com'` },
+  ])
+})
+
+test('NaN and Infinity are rejected, or stored as NULL when configured', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE m (id INTEGER PRIMARY KEY, v REAL)')
+  for (const v of [NaN, Infinity, -Infinity]) {
+    assert.throws(() => db.table('m').insert({ v }), /Cannot store non-finite number/)
+  }
+  assert.throws(() => db.query('SELECT ?', [NaN]), /Cannot store non-finite number NaN/)
+  assert.strictEqual(db.table('m').count(), 0)
+
+  const lenient = new Database(':memory:', { nonFinite: 'null' })
+  lenient.execute('CREATE TABLE m (id INTEGER PRIMARY KEY, v REAL)')
+  lenient.table('m').insert([{ v: NaN }, { v: Infinity }])
+  assert.deepStrictEqual(lenient.query('SELECT typeof(v) AS t FROM m'), [{ t: 'null' }, { t: 'null' }])
+  assert.throws(() => new Database(':memory:', { nonFinite: 'x' }), /Unknown nonFinite mode/)
+})
+
+test('write results carry sql and bindings only in debug mode', () => {
+  const db = seed()
+  const t = db.table('t')
+  assert.deepStrictEqual(t.insert([{ g: 'a' }, { g: 'b' }], { debug: true }), {
+    changes: 2,
+    lastInsertRowid: 2,
+    sql: 'INSERT INTO t ("g") VALUES (?);\nINSERT INTO t ("g") VALUES (?)',
+    bindings: ['a', 'b'],
+  })
+  const plain = t.insert({ g: 'c' }, { result: true })
+  assert.ok(!('sql' in plain) && !('bindings' in plain))
+  assert.deepStrictEqual(t.where('g', 'a').update({ g: 'z' }, { debug: true }), {
+    changes: 1,
+    lastInsertRowid: 3,
+    sql: 'UPDATE t SET "g" = ? WHERE g = ?',
+    bindings: ['z', 'a'],
+  })
+  const deleted = t.where('g', 'b').destroy({ debug: true })
+  assert.strictEqual(deleted.sql, 'DELETE FROM t WHERE g = ?')
+  assert.deepStrictEqual(deleted.bindings, ['b'])
+  assert.ok(!('sql' in t.where('g', 'c').destroy({ result: true })))
+})
+
+test('ignoreUnknown drops keys that are not columns', () => {
+  const db = seed()
+  assert.throws(() => db.table('t').insert({ name: 'a', extra: 1 }), /no column named extra|has no column/)
+  assert.strictEqual(db.table('t').insert({ name: 'a', extra: 1 }, { ignoreUnknown: true }), 1)
+  assert.deepStrictEqual(db.query('SELECT * FROM t'), [{ id: 1, name: 'a', g: undefined }])
+})
+
+test('upsertMany inserts new rows and updates existing ones', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE items (sku TEXT PRIMARY KEY, qty INTEGER, note TEXT)')
+  const rows = (from, to, qty) => Array.from({ length: to - from }, (_, i) => ({ sku: `s${from + i}`, qty, note: 'n' }))
+  db.table('items').insert(rows(0, 500, 1))
+  assert.strictEqual(db.table('items').upsertMany(rows(0, 1000, 2), ['sku']), 1000)
+  assert.deepStrictEqual(db.query('SELECT count(*) AS n, min(qty) AS lo, max(qty) AS hi FROM items'), [{ n: 1000, lo: 2, hi: 2 }])
+  assert.strictEqual(db.table('items').upsertMany(rows(0, 10, 3), ['sku'], ['qty']), 10)
+  assert.deepStrictEqual(db.query("SELECT qty, note FROM items WHERE sku = 's0'"), [{ qty: 3, note: 'n' }])
+})
+
+test('schemaSql returns the CREATE statement', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE)')
+  const sql = db.table('users').schemaSql()
+  assert.match(sql, /^CREATE TABLE users/)
+  assert.ok(sql.includes('email TEXT NOT NULL UNIQUE'))
+  assert.throws(() => db.table('nope').schemaSql(), /Unknown table nope/)
+})
+
+test('row getters check the column type', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER, nick TEXT)')
+  db.table('users').insert({ name: 'Ann', age: 30 })
+  const row = db.table('users').find(1)
+  assert.strictEqual(row.getString('name'), 'Ann')
+  assert.strictEqual(row.getNumber('age'), 30)
+  assert.throws(() => row.getString('age'), /Column age is a number, expected a string/)
+  assert.throws(() => row.getNumber('nope'), /Column nope is not in the row/)
+  assert.throws(() => row.getString('nick'), /NULL/)
+  assert.deepStrictEqual(row.toObject(), { id: 1, name: 'Ann', age: 30, nick: undefined })
+})
+
+test('update and upsertMany write a duplicated column once', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE items (id INTEGER PRIMARY KEY, sku TEXT UNIQUE, name TEXT, qty INTEGER)')
+  db.table('items').insert({ sku: 'a', name: 'old', qty: 1 })
+  const data = Object.create({ name: 'inherited' })
+  data.name = 'new'
+  db.table('items').where('sku', 'a').update(data)
+  assert.strictEqual(db.table('items').find(1).name, 'new')
+  db.table('items').upsertMany([{ sku: 'a', name: 'up', qty: 5 }], ['sku'], ['name', 'qty', 'name'])
+  assert.deepStrictEqual(db.table('items').find(1).toObject(), { id: 1, sku: 'a', name: 'up', qty: 5 })
+})
+
+test('getOrInsert returns the existing row on conflict', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE, name TEXT)')
+  const first = db.table('users').getOrInsert(['email'], { email: 'x@y.z', name: 'First' })
+  const second = db.table('users').getOrInsert(['email'], { email: 'x@y.z', name: 'Second' })
+  assert.deepStrictEqual(second, first)
+  assert.strictEqual(second.name, 'First')
+  assert.strictEqual(db.table('users').count(), 1)
+  assert.throws(
+    () => db.table('users').getOrInsert(['email'], { name: 'x' }),
+    /missing the unique column email/,
+  )
+})
+
+test('streamCsv chunks join to the toCsv output', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE p (id INTEGER PRIMARY KEY, name TEXT, note TEXT)')
+  db.execute(
+    `WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2500)
+     INSERT INTO p (name, note) SELECT 'item ' || i, CASE WHEN i % 3 = 0 THEN 'a,"b"' END FROM n`,
+  )
+  const chunks = []
+  assert.strictEqual(db.table('p').streamCsv((c) => chunks.push(c), { chunkRows: 1000 }), 2500)
+  assert.strictEqual(chunks.length, 3)
+  assert.strictEqual(chunks.join(''), db.table('p').toCsv())
+  const none = []
+  db.table('p').where('id', '>', 9999).streamCsv((c) => none.push(c))
+  assert.deepStrictEqual(none, ['id,name,note\n'])
+})
+
+test('columnInfo reports generated columns', () => {
+  const db = new Database(':memory:')
+  db.execute(
+    'CREATE TABLE items (id INTEGER PRIMARY KEY, price REAL, qty INTEGER, total REAL GENERATED ALWAYS AS (price * qty) VIRTUAL, label TEXT AS (upper(name)) STORED, name TEXT)',
+  )
+  const t = db.table('items')
+  assert.deepStrictEqual(t.columns(), ['id', 'price', 'qty', 'total', 'label', 'name'])
+  assert.deepStrictEqual(
+    t.columnInfo().map((c) => [c.name, c.generated ?? null]),
+    [['id', null], ['price', null], ['qty', null], ['total', 'virtual'], ['label', 'stored'], ['name', null]],
+  )
+  const row = t.find(t.insert({ price: 2.5, qty: 4, name: 'pen' })).toObject()
+  assert.strictEqual(row.total, 10)
+  assert.strictEqual(row.label, 'PEN')
+  delete row.id
+  assert.strictEqual(t.find(t.insert(row)).total, 10)
+})
+
+test('reduce folds every row in order', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE o (id INTEGER PRIMARY KEY, qty INTEGER, weight REAL)')
+  const rows = Array.from({ length: 2500 }, (_, i) => ({ qty: (i + 1) % 7, weight: ((i + 1) % 3) + 0.5 }))
+  db.table('o').insert(rows)
+  const expected = rows.reduce((s, r) => s + r.qty * r.weight, 0)
+  assert.strictEqual(db.table('o').reduce(0, (s, r) => s + r.qty * r.weight), expected)
+  const seen = []
+  db.table('o').where('id', '<=', 3).reduce(null, (_, r, i) => seen.push([r.id, i]))
+  assert.deepStrictEqual(seen, [[1, 0], [2, 1], [3, 2]])
+  assert.strictEqual(db.table('o').where('id', '<', 0).reduce('init', () => 'x'), 'init')
+  assert.throws(
+    () => db.table('o').reduce(0, (s, r) => {
+      if (r.id === 1500) throw new Error('boom')
+      return s
+    }),
+    /boom/,
+  )
+})
+
+test('upsertMany only updates rows passing the where condition', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE d (id INTEGER PRIMARY KEY, body TEXT, updated_at INTEGER)')
+  db.table('d').insert([{ id: 1, body: 'old', updated_at: 10 }, { id: 2, body: 'new', updated_at: 30 }])
+  const changes = db.table('d').upsertMany(
+    [
+      { id: 1, body: 'v2', updated_at: 20 },
+      { id: 2, body: 'stale', updated_at: 20 },
+      { id: 3, body: 'fresh', updated_at: 5 },
+    ],
+    ['id'],
+    undefined,
+    { where: 'excluded.updated_at > updated_at' },
+  )
+  assert.strictEqual(changes, 2)
+  assert.deepStrictEqual(db.query('SELECT id, body FROM d ORDER BY id'), [
+    { id: 1, body: 'v2' },
+    { id: 2, body: 'new' },
+    { id: 3, body: 'fresh' },
+  ])
+  db.table('d').upsertMany([{ id: 1, body: 'x' }, { id: 3, body: 'y' }], ['id'], ['body'], {
+    where: 'd.id <> ?',
+    bindings: [1],
+  })
+  assert.deepStrictEqual(db.query('SELECT body FROM d ORDER BY id').map((r) => r.body), ['v2', 'new', 'y'])
+})
+
+test('typeOf reports the storage class of a cell', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE t (id INTEGER PRIMARY KEY, n INTEGER, r REAL, s TEXT, a)')
+  db.table('t').insert([
+    { n: 42, r: 1, s: 7, a: 1.5 },
+    { n: '42', r: 'x', s: 'hi' },
+  ])
+  const t = db.table('t')
+  assert.strictEqual(t.typeOf(1, 'n'), 'integer')
+  assert.strictEqual(t.typeOf(2, 'n'), 'integer')
+  assert.strictEqual(t.typeOf(1, 'r'), 'real')
+  assert.strictEqual(t.typeOf(2, 'r'), 'text')
+  assert.strictEqual(t.typeOf(1, 's'), 'text')
+  assert.strictEqual(t.typeOf(1, 'a'), 'real')
+  assert.strictEqual(t.typeOf(2, 'a'), 'null')
+  assert.strictEqual(t.typeOf(99, 'n'), null)
+  assert.throws(() => t.typeOf(1, 'missing'), /no such column/)
+})
+
+test('insert returns the new row ids', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE a (id INTEGER PRIMARY KEY AUTOINCREMENT, n TEXT)')
+  db.execute('CREATE TABLE b (id INTEGER PRIMARY KEY, a_id INTEGER, n TEXT)')
+  const id = db.table('a').insert({ n: 'x' })
+  assert.strictEqual(id, 1)
+  assert.deepStrictEqual(db.table('a').insert([{ n: 'y' }, { n: 'z' }]), [2, 3])
+  assert.strictEqual(db.table('b').insert({ a_id: id, n: 'child' }), 1)
+  assert.strictEqual(db.table('b').find(1).a_id, 1)
+})
+
+test('update and destroy return the number of affected rows', () => {
+  const db = new Database(':memory:')
+  db.execute('CREATE TABLE u (id INTEGER PRIMARY KEY, age INTEGER)')
+  db.table('u').insert([{ age: 1 }, { age: 2 }, { age: 3 }, { age: 4 }])
+  assert.strictEqual(db.table('u').where('age', '>', 100).destroy(), 0)
+  assert.strictEqual(db.table('u').where('age', '>', 100).update({ age: 0 }), 0)
+  assert.strictEqual(db.table('u').where('age', '>', 2).update({ age: 9 }), 2)
+  assert.strictEqual(db.table('u').update(99, { age: 5 }), 0)
+  assert.strictEqual(db.table('u').destroy(99), 0)
+  assert.strictEqual(db.table('u').destroy(2), 1)
+  assert.strictEqual(db.table('u').count(), 3)
+})
+
+test('update binds values that look like placeholders', () => {
+  const db = seed()
+  db.table('t').insert([{ name: 'a' }, { name: 'b' }])
+  const [row] = db.table('t').where('id', 1).update({ name: 'a ? b', g: ':g $x @y ?1' }, { returning: true })
+  assert.deepStrictEqual({ name: row.name, g: row.g }, { name: 'a ? b', g: ':g $x @y ?1' })
+  assert.strictEqual(db.table('t').find(2).name, 'b')
+})
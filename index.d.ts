@@ -3,30 +3,273 @@
 
 /* auto-generated by NAPI-RS */
 
+export interface TransactionResult {
+  result: unknown
+  changes: number
+}
+export interface ForeignKeyOptions {
+  check?: boolean
+}
+export interface ForeignKeyViolation {
+  table: string
+  rowid?: number
+  parent: string
+  fkid: number
+}
+export interface ForeignKeyLoadResult {
+  result: unknown
+  violations: Array<ForeignKeyViolation>
+}
+export interface ColumnDescription {
+  name: string
+  declType?: string
+}
+export interface QueryOptions {
+  withTypes?: boolean
+  signal?: object
+}
+export interface JsonOptions {
+  blobEncoding?: string
+}
+export interface ColumnType {
+  name: string
+}
+export interface TypedQueryResult {
+  columns: Array<ColumnType>
+  rows: Array<object>
+}
+export interface StatementDescription {
+  columns: Array<ColumnDescription>
+  paramCount: number
+}
+export interface DatabaseOptions {
+  dateFormat?: string
+  mutex?: string
+  nonFinite?: string
+  encoding?: string
+  cacheSize?: number
+  cacheTtlMs?: number
+  numbersAsStrings?: boolean
+  bigint?: boolean
+  defaultOperator?: string
+}
+export interface InsertOptions {
+  types?: Record<string, string>
+  jsonColumns?: Array<string>
+  result?: boolean
+  debug?: boolean
+  ignoreUnknown?: boolean
+  orIgnore?: boolean
+}
+export interface IndexOptions {
+  name?: string
+  unique?: boolean
+  where?: unknown
+}
+export interface UpsertOptions {
+  where?: string
+  bindings?: Array<unknown>
+}
+export interface RunResult {
+  changes: number
+  lastInsertRowid: number
+  sql?: string
+  bindings?: Array<unknown>
+  ignored?: number
+}
+export interface ColumnInfo {
+  name: string
+  generated?: string
+}
+export interface SyncResult {
+  inserted: number
+  updated: number
+  deleted: number
+}
+export interface CsvOptions {
+  chunkRows?: number
+}
+export interface PaginateOptions {
+  links?: boolean
+}
+export interface Page {
+  data: Array<object>
+  total: number
+  page: number
+  perPage: number
+  lastPage: number
+  nextPage?: number | null
+  prevPage?: number | null
+}
+export interface WriteOptions {
+  limit?: number
+  returning?: boolean
+  result?: boolean
+  debug?: boolean
+}
+export interface AsyncQueryOptions {
+  signal?: object
+}
 export declare class Database {
-  constructor(path: string)
-  execute(sql: string): void
-  query(sql: string): Array<object>
+  constructor(path: string, options?: DatabaseOptions | undefined | null)
+  static open(path: string, options?: DatabaseOptions | undefined | null): Database
+  close(): void
+  interrupt(): void
+  execute(sql: string, params?: Array<unknown> | object | undefined | null): void
+  query(sql: string, params?: Array<unknown> | object | undefined | null, options?: QueryOptions | undefined | null): Array<object> | TypedQueryResult
+  queryAsync(sql: string, params?: Array<unknown> | object | undefined | null, options?: AsyncQueryOptions | undefined | null): Promise<object[]>
+  queryReadonly(sql: string, params?: Array<unknown> | object | undefined | null, options?: QueryOptions | undefined | null): Array<object> | TypedQueryResult
+  queryRows(sql: string, params?: Array<unknown> | object | undefined | null): object
+  getValue(sql: string, params?: Array<unknown> | object | undefined | null): unknown
+  queryJson(sql: string, params?: Array<unknown> | object | undefined | null, options?: JsonOptions | undefined | null): string
+  queryWith(sql: string, params: Array<unknown> | object | undefined | null, mapper: (...args: any[]) => any): Array<unknown>
+  clearCache(): void
+  cacheSize(): number
+  describe(sql: string): StatementDescription
+  fileSize(): number
+  freelistCount(): number
+  reindex(target?: string | undefined | null): void
+  optimize(): void
+  vacuumInto(path: string): void
+  pragmaRows(name: string, arg?: string | number | undefined | null): Array<object>
+  static escapeLike(input: string): string
+  static enumColumn(name: string, values: Array<string>): string
+  tables(): Array<string>
+  views(): Array<string>
+  begin(behavior?: string | undefined | null): void
+  commit(): void
+  rollback(): void
+  inTransaction(): boolean
+  transaction(callback: (...args: any[]) => any, behavior?: string | undefined | null): TransactionResult
+  withoutForeignKeys(callback: (...args: any[]) => any, options?: ForeignKeyOptions | undefined | null): ForeignKeyLoadResult
+  migrate(migrations: Array<object>): Array<string>
+  renameTable(oldName: string, newName: string): void
+  withCte(name: string, sql: string): Database
+  withTempRows(name: string, rows: Array<object>): Table
   table(name: string): Table
 }
 export declare class Table {
-  find(id: number | string): object | null
+  first(): object | null
+  last(): object | null
+  find(id: string | number): object | null
+  typeOf(id: string | number, column: string): string | null
+  getBy(column: string, value: string | number | boolean): object | null
   get(): Array<object>
   all(): Array<object>
-  where(column: string, opOrValue: string | string | number, valueOpt?: string | number | undefined | null): FilteredTable
-  insert(data: unknown): void
-  create(data: unknown): void
-  update(id: string | number, data: object): void
+  allAsync(options?: AsyncQueryOptions | undefined | null): Promise<object[]>
+  latestPer(groupColumn: string, orderColumn: string): Array<object>
+  where(columnOrEqualities: string | object, opOrValue?: string | string | number | boolean | undefined | null, valueOpt?: string | number | boolean | undefined | null): FilteredTable
+  select(columns: Array<string>): FilteredTable
+  as(alias: string): Table
+  whereRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  selectRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  groupBy(columns: Array<string>): FilteredTable
+  havingRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  orderByRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  paginate(page: number, perPage: number, options?: PaginateOptions | undefined | null): Page
+  limit(n: number): FilteredTable
+  offset(n: number): FilteredTable
+  whereJsonMatch(column: string, paths: object): FilteredTable
+  whereEnum(column: string, value: string): FilteredTable
+  whereStartsWith(column: string, prefix: string): FilteredTable
+  whereEndsWith(column: string, suffix: string): FilteredTable
+  whereIn(column: string, values: Array<unknown>): FilteredTable
+  whereInQuery(column: string, query: FilteredTable): FilteredTable
+  whereFn(funcName: string, args: Array<string>, operator: string, value?: string | number | boolean | undefined | null): FilteredTable
+  within(minX: number, maxX: number, minY: number, maxY: number): FilteredTable
+  whereInRange(column: string, start: unknown, end: unknown): FilteredTable
+  whereTimestampBetween(column: string, start: unknown, end: unknown): FilteredTable
+  whereJsonContains(column: string, path: string, value: string | number | boolean): FilteredTable
+  whereIlike(column: string, pattern: string): FilteredTable
+  whereNot(columnOrGroup: string | ((...args: any[]) => any), opOrValue?: string | string | number | boolean | undefined | null, valueOpt?: string | number | boolean | undefined | null): FilteredTable
+  columns(): Array<string>
+  columnInfo(): Array<ColumnInfo>
+  schemaSql(): string
+  renameColumn(oldName: string, newName: string): void
+  addColumn(name: string, typeSpec: string): void
+  dropColumn(name: string): void
+  createIndex(columns: Array<string>, options?: IndexOptions | undefined | null): string
+  hasMany(name: string, table: string, foreignKey: string, localKey?: string | undefined | null): Table
+  belongsTo(name: string, table: string, foreignKey: string, ownerKey?: string | undefined | null): Table
+  withJson(relation: string): FilteredTable
+  has(relation: string): FilteredTable
+  doesntHave(relation: string): FilteredTable
+  insert(data: unknown, options?: InsertOptions | undefined | null): number | Array<number> | RunResult
+  sync(keyColumn: string, rows: Array<object>): SyncResult
+  copyTo(destTable: string, filter?: FilteredTable | undefined | null): number
+  upsertMany(rows: Array<object>, conflictColumns: Array<string>, updateColumns?: Array<string> | undefined | null, options?: UpsertOptions | undefined | null): number
+  getOrInsert(uniqueColumns: Array<string>, data: object): object
+  count(): number | bigint
+  sum(column: string): number | null
+  avg(column: string): number | null
+  min(column: string): number | null
+  max(column: string): number | null
+  countDistinct(column: string): number | bigint
+  lockForUpdate(): Array<object>
+  reduce(initial: unknown, reducer: (...args: any[]) => any): unknown
+  toCsv(): string
+  streamCsv(callback: (...args: any[]) => any, options?: CsvOptions | undefined | null): number
+  create(data: unknown, options?: InsertOptions | undefined | null): number | Array<number> | RunResult
+  update(id: string | number, data: object, options?: WriteOptions | undefined | null): number | Array<object> | RunResult
+  latest(column?: string | undefined | null): FilteredTable
+  oldest(column?: string | undefined | null): FilteredTable
+  orderBySafe(column: string, direction: string | undefined | null, allowedColumns: Array<string>): FilteredTable
   orderBy(column: string, direction?: string | undefined | null): FilteredTable
-  destroy(id: string | number): void
+  destroy(id: string | number, options?: WriteOptions | undefined | null): number | Array<object> | RunResult
 }
 export declare class FilteredTable {
   first(): object | null
   last(): object | null
+  firstOrFail(): object
+  toSql(): string
+  cloneBuilder(): FilteredTable
+  orderBySafe(column: string, direction: string | undefined | null, allowedColumns: Array<string>): FilteredTable
+  limit(n: number): FilteredTable
+  offset(n: number): FilteredTable
+  paginate(page: number, perPage: number, options?: PaginateOptions | undefined | null): Page
   orderBy(column: string, direction?: string | undefined | null): FilteredTable
-  where(column: string, opOrValue: string | string | number, valueOpt?: string | number | undefined | null): FilteredTable
+  latest(column?: string | undefined | null): FilteredTable
+  oldest(column?: string | undefined | null): FilteredTable
+  where(columnOrEqualities: string | object, opOrValue?: string | string | number | boolean | undefined | null, valueOpt?: string | number | boolean | undefined | null): FilteredTable
+  select(columns: Array<string>): FilteredTable
+  whereRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  selectRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  groupBy(columns: Array<string>): FilteredTable
+  havingRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  orderByRaw(sql: string, bindings?: Array<unknown> | undefined | null): FilteredTable
+  bindings(): Array<unknown>
+  whereIn(column: string, values: Array<unknown>): FilteredTable
+  whereInQuery(column: string, query: FilteredTable): FilteredTable
+  whereFn(funcName: string, args: Array<string>, operator: string, value?: string | number | boolean | undefined | null): FilteredTable
+  within(minX: number, maxX: number, minY: number, maxY: number): FilteredTable
+  whereIlike(column: string, pattern: string): FilteredTable
+  whereEnum(column: string, value: string): FilteredTable
+  whereStartsWith(column: string, prefix: string): FilteredTable
+  whereEndsWith(column: string, suffix: string): FilteredTable
+  whereInRange(column: string, start: unknown, end: unknown): FilteredTable
+  whereTimestampBetween(column: string, start: unknown, end: unknown): FilteredTable
+  whereJsonContains(column: string, path: string, value: string | number | boolean): FilteredTable
+  whereJsonMatch(column: string, paths: object): FilteredTable
+  whereNot(columnOrGroup: string | ((...args: any[]) => any), opOrValue?: string | string | number | boolean | undefined | null, valueOpt?: string | number | boolean | undefined | null): FilteredTable
+  has(relation: string): FilteredTable
+  doesntHave(relation: string): FilteredTable
+  withJson(relation: string): FilteredTable
   get(): Array<object>
+  asArray(): object
   all(): Array<object>
-  update(data: object): void
-  set destroy()
+  allAsync(options?: AsyncQueryOptions | undefined | null): Promise<object[]>
+  reduce(initial: unknown, reducer: (...args: any[]) => any): unknown
+  toCsv(): string
+  streamCsv(callback: (...args: any[]) => any, options?: CsvOptions | undefined | null): number
+  count(): number | bigint
+  sum(column: string): number | null
+  avg(column: string): number | null
+  min(column: string): number | null
+  max(column: string): number | null
+  countDistinct(column: string): number | bigint
+  lockForUpdate(): Array<object>
+  destroy(options?: WriteOptions | undefined | null): number | Array<object> | RunResult
+  replace(data: object): void
+  updateRaw(setFragment: string, bindings?: Array<unknown> | undefined | null): void
+  update(data: object, options?: WriteOptions | undefined | null): number | Array<object> | RunResult
 }
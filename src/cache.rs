@@ -0,0 +1,257 @@
+use napi::{Env, JsObject, Result};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::extra::{
+  collect_rows, column_keys, column_to_js, quote_identifier, split_table_name, Config, SqlParams,
+};
+
+pub(crate) type SharedCache = Option<Arc<Mutex<QueryCache>>>;
+
+pub struct CachedRows {
+  pub(crate) columns: Vec<String>,
+  pub(crate) rows: Vec<Vec<Value>>,
+}
+
+// Result cache for read queries, keyed by SQL plus bindings. Entries are plain SQLite
+// values so every hit still builds fresh JS objects.
+pub(crate) struct QueryCache {
+  capacity: usize,
+  ttl: Duration,
+  entries: HashMap<String, (Instant, Arc<CachedRows>)>,
+  // Insertion order, oldest first, for evicting once `capacity` is reached
+  order: VecDeque<String>,
+  version: (i64, i64, i64),
+}
+
+impl QueryCache {
+  pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+    QueryCache {
+      capacity,
+      ttl,
+      entries: HashMap::new(),
+      order: VecDeque::new(),
+      version: (0, 0, 0),
+    }
+  }
+
+  pub(crate) fn clear(&mut self) {
+    self.entries.clear();
+    self.order.clear();
+  }
+
+  pub(crate) fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  // Drops everything once any write has happened since the entries were stored:
+  // total_changes() covers this connection, data_version other connections and
+  // schema_version DDL (which total_changes doesn't count)
+  pub(crate) fn sync_version(&mut self, conn: &Connection) -> rusqlite::Result<()> {
+    let version = conn.query_row(
+      "SELECT total_changes(), (SELECT data_version FROM pragma_data_version), \
+             (SELECT schema_version FROM pragma_schema_version)",
+      [],
+      |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    if version != self.version {
+      self.clear();
+      self.version = version;
+    }
+    Ok(())
+  }
+
+  pub(crate) fn get(&mut self, key: &str) -> Option<Arc<CachedRows>> {
+    let (stored, rows) = self.entries.get(key)?;
+    if stored.elapsed() <= self.ttl {
+      return Some(rows.clone());
+    }
+    self.entries.remove(key);
+    self.order.retain(|k| k != key);
+    None
+  }
+
+  pub(crate) fn insert(&mut self, key: String, rows: Arc<CachedRows>) {
+    if self.capacity == 0 {
+      return;
+    }
+    if self
+      .entries
+      .insert(key.clone(), (Instant::now(), rows))
+      .is_some()
+    {
+      self.order.retain(|k| *k != key);
+    }
+    self.order.push_back(key);
+    while self.order.len() > self.capacity {
+      if let Some(oldest) = self.order.pop_front() {
+        self.entries.remove(&oldest);
+      }
+    }
+  }
+}
+
+pub(crate) fn cached_query(
+  cache: &Mutex<QueryCache>,
+  conn: &Connection,
+  sql: &str,
+  params: SqlParams,
+  require_readonly: bool,
+) -> Result<Arc<CachedRows>> {
+  let key = format!("{}\0{}", sql, params.cache_key());
+  let mut cache = cache.lock().unwrap();
+  cache
+    .sync_version(conn)
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+  if let Some(hit) = cache.get(&key) {
+    return Ok(hit);
+  }
+
+  let (rows, readonly) = query_values(conn, sql, params, require_readonly)?;
+  let rows = Arc::new(rows);
+  // Statements that write (e.g. RETURNING) or return no columns are never served from the cache
+  if readonly && !rows.columns.is_empty() {
+    cache.insert(key, rows.clone());
+  }
+  Ok(rows)
+}
+
+// Runs a query into plain values, also reporting whether the statement was read-only
+pub(crate) fn query_values(
+  conn: &Connection,
+  sql: &str,
+  params: SqlParams,
+  require_readonly: bool,
+) -> Result<(CachedRows, bool)> {
+  let mut stmt = conn
+    .prepare(sql)
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+  let readonly = stmt.readonly();
+  if require_readonly && !readonly {
+    return Err(napi::Error::from_reason(
+      "Statement is not read-only and was rejected by query_readonly",
+    ));
+  }
+
+  let columns: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+  // Statements without result columns (DDL, PRAGMA optimize, ...) still run for their
+  // effect but never produce rows, rather than a list of empty objects
+  if columns.is_empty() {
+    collect_rows(&mut stmt, params, |_| Ok(()))?;
+    return Ok((
+      CachedRows {
+        columns,
+        rows: Vec::new(),
+      },
+      readonly,
+    ));
+  }
+
+  let rows = collect_rows(&mut stmt, params, |row| {
+    (0..columns.len())
+      .map(|i| row.get::<_, Value>(i))
+      .collect::<rusqlite::Result<Vec<_>>>()
+  })?;
+  Ok((CachedRows { columns, rows }, readonly))
+}
+
+pub(crate) fn cached_rows_to_objects(
+  env: Env,
+  cached: &CachedRows,
+  config: &Config,
+) -> Result<Vec<JsObject>> {
+  let keys = column_keys(env, &cached.columns)?;
+  cached
+    .rows
+    .iter()
+    .map(|values| {
+      let mut obj = env.create_object()?;
+      for (key, value) in keys.iter().zip(values) {
+        obj.set_property(*key, column_to_js(env, value.clone(), config)?)?;
+      }
+      Ok(obj)
+    })
+    .collect()
+}
+
+#[derive(Clone)]
+pub(crate) struct ColumnMeta {
+  pub(crate) name: String,
+  // table_xinfo's flag: 0 plain, 1 hidden virtual-table column, 2 VIRTUAL and 3 STORED generated
+  pub(crate) hidden: i64,
+}
+
+impl ColumnMeta {
+  pub(crate) fn generated(&self) -> Option<&'static str> {
+    match self.hidden {
+      2 => Some("virtual"),
+      3 => Some("stored"),
+      _ => None,
+    }
+  }
+}
+
+// Columns per table, tagged with the schema_version they were read at
+pub(crate) type SchemaCache = Arc<Mutex<HashMap<String, (i64, Vec<ColumnMeta>)>>>;
+
+// table_xinfo rather than table_info, which leaves out generated columns
+pub(crate) fn table_column_meta(
+  schema: &SchemaCache,
+  conn: &Connection,
+  table: &str,
+) -> Result<Vec<ColumnMeta>> {
+  let (schema_prefix, name) = split_table_name(table);
+  let version: i64 = conn
+    .query_row(
+      &format!("PRAGMA {}schema_version", schema_prefix),
+      [],
+      |row| row.get(0),
+    )
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+  let mut schema = schema.lock().unwrap();
+  if let Some((cached_version, columns)) = schema.get(table) {
+    if *cached_version == version {
+      return Ok(columns.clone());
+    }
+  }
+
+  let mut stmt = conn
+    .prepare(&format!(
+      "PRAGMA {}table_xinfo({})",
+      schema_prefix,
+      quote_identifier(name)
+    ))
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+  let columns = stmt
+    .query_map([], |row| {
+      Ok(ColumnMeta {
+        name: row.get("name")?,
+        hidden: row.get("hidden")?,
+      })
+    })
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?
+    .collect::<rusqlite::Result<Vec<_>>>()
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+  schema.insert(table.to_string(), (version, columns.clone()));
+  Ok(columns)
+}
+
+// Column names, generated columns included but not the hidden columns of virtual tables
+pub(crate) fn table_columns(
+  schema: &SchemaCache,
+  conn: &Connection,
+  table: &str,
+) -> Result<Vec<String>> {
+  Ok(
+    table_column_meta(schema, conn, table)?
+      .into_iter()
+      .filter(|column| column.hidden != 1)
+      .map(|column| column.name)
+      .collect(),
+  )
+}
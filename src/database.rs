@@ -1,65 +1,1127 @@
-use napi::{Env, JsObject, Result};
+use napi::bindgen_prelude::{AsyncTask, This};
+use napi::{Env, JsFunction, JsObject, JsUnknown, Result};
 use napi_derive::napi;
-use rusqlite::{Connection};
-use std::sync::{Arc, Mutex};
+use rusqlite::types::Value;
+use rusqlite::{Connection, InterruptHandle, OpenFlags};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+use std::time::Duration;
 
-use crate::extra::{row_to_object};
-use crate::table::{Table};
+use crate::cache::{
+  cached_query, cached_rows_to_objects, query_values, QueryCache, SchemaCache, SharedCache,
+};
+use crate::extra::{
+  collect_rows, column_to_js, enum_column_sql, escape_like, id_to_value, is_valid_identifier,
+  is_valid_qualified_identifier, js_object_to_hashmap, js_unknown_to_bind_value, json_value,
+  lock_connection, query_to_arrays, quote_identifier, row_to_object, sql_literal, total_changes,
+  BlobEncoding, Config, DateFormat, DefaultOperator, JsParams, NonFinite, SqlParams,
+};
+use crate::table::{Relations, Table, TempTable};
+use crate::task::{check_signal, AsyncQueryOptions, QueryTask};
+
+#[napi(object)]
+pub struct TransactionResult {
+  pub result: JsUnknown,
+  pub changes: i64,
+}
+
+#[napi(object)]
+pub struct ForeignKeyOptions {
+  // Run `PRAGMA foreign_key_check` once the callback returns (default true)
+  pub check: Option<bool>,
+}
+
+#[napi(object)]
+pub struct ForeignKeyViolation {
+  pub table: String,
+  pub rowid: Option<i64>,
+  pub parent: String,
+  pub fkid: i64,
+}
+
+#[napi(object)]
+pub struct ForeignKeyLoadResult {
+  pub result: JsUnknown,
+  pub violations: Vec<ForeignKeyViolation>,
+}
+
+#[napi(object)]
+pub struct ColumnDescription {
+  pub name: String,
+  pub decl_type: Option<String>,
+}
+
+#[napi(object)]
+pub struct QueryOptions {
+  pub with_types: Option<bool>,
+  // An AbortSignal; an already-aborted signal rejects the query before it runs
+  pub signal: Option<JsObject>,
+}
+
+#[napi(object)]
+pub struct JsonOptions {
+  pub blob_encoding: Option<String>,
+}
+
+#[napi(object)]
+pub struct ColumnType {
+  pub name: String,
+  pub r#type: String,
+}
+
+#[napi(object)]
+pub struct TypedQueryResult {
+  pub columns: Vec<ColumnType>,
+  pub rows: Vec<JsObject>,
+}
+
+#[napi(object)]
+pub struct StatementDescription {
+  pub columns: Vec<ColumnDescription>,
+  pub param_count: u32,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct DatabaseOptions {
+  pub date_format: Option<String>,
+  pub mutex: Option<String>,
+  pub non_finite: Option<String>,
+  pub encoding: Option<String>,
+  pub cache_size: Option<u32>,
+  pub cache_ttl_ms: Option<u32>,
+  pub numbers_as_strings: Option<bool>,
+  pub bigint: Option<bool>,
+  pub default_operator: Option<String>,
+}
 
 #[napi]
 pub struct Database {
-    conn: Arc<Mutex<Connection>>,
+  conn: Arc<Mutex<Connection>>,
+  config: Config,
+  relations: Relations,
+  ctes: Vec<(String, String)>,
+  cache: SharedCache,
+  schema: SchemaCache,
+  // Usable without the connection lock, so a running statement can be stopped
+  interrupt: Arc<InterruptHandle>,
+  // Shared by every handle and table on the connection, set once it is closed
+  closed: Arc<AtomicBool>,
+  // Path the handle was registered under by `Database.open`
+  registry_key: Option<String>,
+  // Set on withCte handles, which share their parent's connection and so never close it
+  borrowed: bool,
+  // Set by `close` on this handle, whether or not that closed the connection
+  released: bool,
+}
+
+struct RegistryEntry {
+  database: Database,
+  handles: usize,
+}
+
+// Connections opened through `Database.open`, shared per file within the process
+static REGISTRY: LazyLock<Mutex<HashMap<String, RegistryEntry>>> = LazyLock::new(Default::default);
+
+impl Database {
+  fn share(&self) -> Database {
+    Database {
+      conn: self.conn.clone(),
+      config: self.config.clone(),
+      relations: self.relations.clone(),
+      ctes: self.ctes.clone(),
+      cache: self.cache.clone(),
+      schema: self.schema.clone(),
+      interrupt: self.interrupt.clone(),
+      closed: self.closed.clone(),
+      registry_key: None,
+      borrowed: false,
+      released: false,
+    }
+  }
+
+  fn check_open(&self) -> Result<()> {
+    if self.released || self.closed.load(Ordering::SeqCst) {
+      return Err(napi::Error::from_reason("The database is closed"));
+    }
+    Ok(())
+  }
+
+  fn lock(&self) -> Result<MutexGuard<'_, Connection>> {
+    self.check_open()?;
+    lock_connection(&self.conn, &self.closed)
+  }
+}
+
+// The key has to be the same before and after the file is created, so only the directory,
+// which exists either way, is canonicalized
+fn registry_key(path: &str) -> String {
+  let path = Path::new(path);
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir,
+    _ => Path::new("."),
+  };
+  match (std::fs::canonicalize(dir), path.file_name()) {
+    (Ok(dir), Some(name)) => dir.join(name).to_string_lossy().into_owned(),
+    _ => path.to_string_lossy().into_owned(),
+  }
 }
 
 #[napi]
 impl Database {
-    #[napi(constructor)]
-    pub fn new(path: String) -> Result<Self> {
-        let conn = Connection::open(path)
-            .map_err(|e| napi::Error::from_reason(format!("Failed to open db: {}", e)))?;
-        Ok(Database {
-            conn: Arc::new(Mutex::new(conn)),
-        })
+  #[napi(constructor)]
+  pub fn new(path: String, options: Option<DatabaseOptions>) -> Result<Self> {
+    let options = options.unwrap_or_default();
+
+    let mut config = Config::default();
+    if let Some(format) = options.date_format {
+      config.date_format = DateFormat::parse(&format)?;
+    }
+    if let Some(mode) = options.non_finite {
+      config.non_finite = NonFinite::parse(&mode)?;
+    }
+    config.numbers_as_strings = options.numbers_as_strings.unwrap_or(false);
+    config.bigint = options.bigint.unwrap_or(false);
+    if let Some(operator) = options.default_operator {
+      config.default_operator = DefaultOperator::parse(&operator)?;
     }
 
-    #[napi]
-    pub fn execute(&self, sql: String) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute_batch(&sql)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
-        Ok(())
+    let mut flags = OpenFlags::default();
+    match options.mutex.as_deref() {
+      None | Some("no") => {}
+      Some("full") => {
+        flags.remove(OpenFlags::SQLITE_OPEN_NO_MUTEX);
+        flags.insert(OpenFlags::SQLITE_OPEN_FULL_MUTEX);
+      }
+      Some(other) => {
+        return Err(napi::Error::from_reason(format!(
+          "Unknown mutex mode '{}', expected 'no' or 'full'",
+          other
+        )))
+      }
     }
 
-    #[napi]
-    pub fn query(&self, env: Env, sql: String) -> Result<Vec<JsObject>> {
-        let conn = self.conn.lock().unwrap();
+    let conn = Connection::open_with_flags(path, flags)
+      .map_err(|e| napi::Error::from_reason(format!("Failed to open db: {}", e)))?;
 
-        let mut stmt = conn
-            .prepare(&sql)
-            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    if let Some(encoding) = options.encoding {
+      set_encoding(&conn, &encoding)?;
+    }
+
+    let cache = match options.cache_size {
+      Some(size) if size > 0 => Some(Arc::new(Mutex::new(QueryCache::new(
+        size as usize,
+        Duration::from_millis(options.cache_ttl_ms.unwrap_or(1000) as u64),
+      )))),
+      _ => None,
+    };
+    Ok(Database {
+      interrupt: Arc::new(conn.get_interrupt_handle()),
+      conn: Arc::new(Mutex::new(conn)),
+      config,
+      relations: Default::default(),
+      ctes: Vec::new(),
+      cache,
+      schema: Default::default(),
+      closed: Default::default(),
+      registry_key: None,
+      borrowed: false,
+      released: false,
+    })
+  }
+
+  // Returns a handle on the process-wide connection for `path`, opening it on first use.
+  // In-memory databases are never shared, and options only apply to the first open.
+  #[napi(factory)]
+  pub fn open(path: String, options: Option<DatabaseOptions>) -> Result<Self> {
+    if path.is_empty() || path == ":memory:" {
+      return Database::new(path, options);
+    }
+    let key = registry_key(&path);
+
+    let mut registry = REGISTRY.lock().unwrap();
+    let entry = match registry.entry(key.clone()) {
+      Entry::Occupied(entry) => entry.into_mut(),
+      Entry::Vacant(entry) => entry.insert(RegistryEntry {
+        database: Database::new(key.clone(), options)?,
+        handles: 0,
+      }),
+    };
+    entry.handles += 1;
+    let mut database = entry.database.share();
+    database.registry_key = Some(key);
+    Ok(database)
+  }
+
+  // Closes the connection. A handle from `Database.open` only releases its reference,
+  // and the connection is closed once the last handle for the path is closed. withCte
+  // handles borrow their parent's connection, so closing one only releases that handle.
+  // Either way, later calls on the handle fail.
+  #[napi]
+  pub fn close(&mut self) -> Result<()> {
+    if self.released {
+      return Ok(());
+    }
+    self.released = true;
+
+    if let Some(key) = &self.registry_key {
+      let mut registry = REGISTRY.lock().unwrap();
+      if let Some(entry) = registry.get_mut(key) {
+        entry.handles -= 1;
+        if entry.handles > 0 {
+          return Ok(());
+        }
+        registry.remove(key);
+      }
+    } else if self.borrowed {
+      return Ok(());
+    }
+
+    // Tables built from this handle share the connection, so swap in a placeholder
+    // and close the file now rather than when the last of them is garbage collected.
+    // The flag makes their calls fail rather than run on the placeholder.
+    let placeholder =
+      Connection::open_in_memory().map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    let mut guard = self.conn.lock().unwrap();
+    self.closed.store(true, Ordering::SeqCst);
+    let conn = std::mem::replace(&mut *guard, placeholder);
+    conn
+      .close()
+      .map_err(|(_, e)| napi::Error::from_reason(format!("Failed to close db: {}", e)))
+  }
+
+  // Stops the statement currently running on this connection, which then fails with
+  // an "interrupted" error; a no-op when nothing is running
+  #[napi]
+  pub fn interrupt(&self) {
+    self.interrupt.interrupt();
+  }
+
+  #[napi]
+  pub fn execute(&self, sql: String, params: Option<JsParams>) -> Result<()> {
+    let conn = self.lock()?;
+    match params {
+      Some(params) => {
+        let params = SqlParams::from_js(Some(params), &self.config)?;
+        let mut batch = rusqlite::Batch::new(&conn, &sql);
+        let Some(mut stmt) = batch
+          .next()
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?
+        else {
+          return Ok(());
+        };
+        // Parameters bind to a single statement, so anything after it would silently never
+        // run (a later statement failing to prepare counts as one too)
+        if !matches!(batch.next(), Ok(None)) {
+          return Err(napi::Error::from_reason(
+            "execute with params runs a single statement; call it once per statement",
+          ));
+        }
+        params.bind(&mut stmt)?;
+        stmt
+          .raw_execute()
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      }
+      None => {
+        conn
+          .execute_batch(&sql)
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      }
+    }
+    Ok(())
+  }
+
+  #[napi]
+  pub fn query(
+    &self,
+    env: Env,
+    sql: String,
+    params: Option<JsParams>,
+    options: Option<QueryOptions>,
+  ) -> Result<napi::Either<Vec<JsObject>, TypedQueryResult>> {
+    self.run_query(env, &sql, params, false, options)
+  }
+
+  // `query` on the libuv threadpool, returning a promise so a slow query doesn't block the
+  // event loop. Other calls on this database wait for the connection while it runs.
+  #[napi(ts_return_type = "Promise<object[]>")]
+  pub fn query_async(
+    &self,
+    env: Env,
+    sql: String,
+    params: Option<JsParams>,
+    options: Option<AsyncQueryOptions>,
+  ) -> Result<AsyncTask<QueryTask>> {
+    let params = SqlParams::from_js(params, &self.config)?.into_owned();
+    let task = QueryTask::new(
+      env,
+      (
+        self.conn.clone(),
+        self.interrupt.clone(),
+        self.closed.clone(),
+      ),
+      self.cache.clone(),
+      self.config.clone(),
+      (sql, params),
+      Vec::new(),
+      options.and_then(|o| o.signal),
+    )?;
+    Ok(AsyncTask::new(task))
+  }
+
+  #[napi]
+  pub fn query_readonly(
+    &self,
+    env: Env,
+    sql: String,
+    params: Option<JsParams>,
+    options: Option<QueryOptions>,
+  ) -> Result<napi::Either<Vec<JsObject>, TypedQueryResult>> {
+    self.run_query(env, &sql, params, true, options)
+  }
+
+  #[napi]
+  pub fn query_rows(&self, env: Env, sql: String, params: Option<JsParams>) -> Result<JsObject> {
+    let params = SqlParams::from_js(params, &self.config)?;
+
+    let conn = self.lock()?;
+    query_to_arrays(env, &conn, &sql, params, &self.config)
+  }
+
+  // The first column of the first row, e.g. `SELECT max(id) FROM t`, converted like any other
+  // column value (so NULL reads as undefined); null when there is no row at all
+  #[napi]
+  pub fn get_value(&self, env: Env, sql: String, params: Option<JsParams>) -> Result<JsUnknown> {
+    let params = SqlParams::from_js(params, &self.config)?;
+
+    let conn = self.lock()?;
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    if stmt.column_count() == 0 {
+      return Err(napi::Error::from_reason(
+        "getValue needs a statement that returns a column",
+      ));
+    }
+    params.bind(&mut stmt)?;
+    let mut rows = stmt.raw_query();
+    match rows
+      .next()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?
+    {
+      Some(row) => {
+        let value = row
+          .get::<_, Value>(0)
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        column_to_js(env, value, &self.config)
+      }
+      None => env.get_null().map(|v| v.into_unknown()),
+    }
+  }
+
+  // Rows serialized straight to a JSON string; blobs become base64 (or hex / byte arrays)
+  // and non-finite reals become null so the output always round-trips through JSON.parse
+  #[napi]
+  pub fn query_json(
+    &self,
+    sql: String,
+    params: Option<JsParams>,
+    options: Option<JsonOptions>,
+  ) -> Result<String> {
+    let blobs = match options.and_then(|o| o.blob_encoding) {
+      Some(name) => BlobEncoding::parse(&name)?,
+      None => BlobEncoding::Base64,
+    };
+    let params = SqlParams::from_js(params, &self.config)?;
+
+    let conn = self.lock()?;
+    let result = match &self.cache {
+      Some(cache) => cached_query(cache, &conn, &sql, params, false)?,
+      None => Arc::new(query_values(&conn, &sql, params, false)?.0),
+    };
+    drop(conn);
+
+    let mut out = String::from("[");
+    for (i, values) in result.rows.iter().enumerate() {
+      if i > 0 {
+        out.push(',');
+      }
+      out.push('{');
+      for (j, (column, value)) in result.columns.iter().zip(values).enumerate() {
+        if j > 0 {
+          out.push(',');
+        }
+        json_value(&Value::Text(column.clone()), blobs, &mut out);
+        out.push(':');
+        json_value(value, blobs, &mut out);
+      }
+      out.push('}');
+    }
+    out.push(']');
+    Ok(out)
+  }
+
+  #[napi]
+  pub fn query_with(
+    &self,
+    env: Env,
+    sql: String,
+    params: Option<JsParams>,
+    mapper: JsFunction,
+  ) -> Result<Vec<JsUnknown>> {
+    let params = SqlParams::from_js(params, &self.config)?;
+
+    // Collect plain values first so the mapper runs without holding the connection lock
+    let (column_names, rows) = {
+      let conn = self.lock()?;
+      let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+      let rows = collect_rows(&mut stmt, params, |row| {
+        (0..column_names.len())
+          .map(|i| row.get::<_, Value>(i))
+          .collect::<rusqlite::Result<Vec<_>>>()
+      })?;
+      (column_names, rows)
+    };
+
+    rows
+      .into_iter()
+      .map(|values| {
+        let mut pairs = env.create_array_with_length(values.len())?;
+        for (i, (name, value)) in column_names.iter().zip(values).enumerate() {
+          let mut pair = env.create_array_with_length(2)?;
+          pair.set_element(0, env.create_string(name)?)?;
+          pair.set_element(1, column_to_js(env, value, &self.config)?)?;
+          pairs.set_element(i as u32, pair)?;
+        }
+        mapper.call(None, &[pairs])
+      })
+      .collect()
+  }
+
+  fn run_query(
+    &self,
+    env: Env,
+    sql: &str,
+    params: Option<JsParams>,
+    readonly: bool,
+    options: Option<QueryOptions>,
+  ) -> Result<napi::Either<Vec<JsObject>, TypedQueryResult>> {
+    let (with_types, signal) = options.map_or((None, None), |o| (o.with_types, o.signal));
+    check_signal(signal.as_ref())?;
+    let params = SqlParams::from_js(params, &self.config)?;
+
+    let conn = self.lock()?;
+    let result = match &self.cache {
+      Some(cache) => cached_query(cache, &conn, sql, params, readonly)?,
+      None => Arc::new(query_values(&conn, sql, params, readonly)?.0),
+    };
+    drop(conn);
 
-        let column_names: Vec<String> =
-            stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let rows = cached_rows_to_objects(env, &result, &self.config)?;
+    if !with_types.unwrap_or(false) {
+      return Ok(napi::Either::A(rows));
+    }
+
+    // Storage classes are taken from the first row; an empty result reports NULL
+    let first = result.rows.first();
+    let columns = result
+      .columns
+      .iter()
+      .enumerate()
+      .map(|(i, name)| ColumnType {
+        name: name.clone(),
+        r#type: first
+          .map_or("NULL", |row| storage_class(&row[i]))
+          .to_string(),
+      })
+      .collect();
+    Ok(napi::Either::B(TypedQueryResult { columns, rows }))
+  }
+
+  #[napi]
+  pub fn clear_cache(&self) {
+    if let Some(cache) = &self.cache {
+      cache.lock().unwrap().clear();
+    }
+  }
+
+  #[napi]
+  pub fn cache_size(&self) -> u32 {
+    self
+      .cache
+      .as_ref()
+      .map_or(0, |cache| cache.lock().unwrap().len() as u32)
+  }
+
+  #[napi]
+  pub fn describe(&self, sql: String) -> Result<StatementDescription> {
+    let conn = self.lock()?;
+    let stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    let columns = stmt
+      .columns()
+      .iter()
+      .map(|col| ColumnDescription {
+        name: col.name().to_string(),
+        decl_type: col.decl_type().map(|t| t.to_string()),
+      })
+      .collect();
+
+    Ok(StatementDescription {
+      columns,
+      param_count: stmt.parameter_count() as u32,
+    })
+  }
+
+  // Size of the main database in bytes (page_count * page_size), for file and memory databases alike
+  #[napi]
+  pub fn file_size(&self) -> Result<i64> {
+    let conn = self.lock()?;
+    conn
+      .query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+      )
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  // Unused pages waiting to be reused or reclaimed by VACUUM
+  #[napi]
+  pub fn freelist_count(&self) -> Result<i64> {
+    let conn = self.lock()?;
+    conn
+      .query_row("PRAGMA freelist_count", [], |row| row.get(0))
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  // Rebuilds every index, or only those of one table / collation or a single index
+  #[napi]
+  pub fn reindex(&self, target: Option<String>) -> Result<()> {
+    let sql = match target {
+      Some(target) if !is_valid_identifier(&target) => {
+        return Err(napi::Error::from_reason(format!(
+          "Invalid reindex target: {}",
+          target
+        )));
+      }
+      Some(target) => format!("REINDEX {}", quote_identifier(&target)),
+      None => "REINDEX".to_string(),
+    };
+    let conn = self.lock()?;
+    conn
+      .execute_batch(&sql)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  #[napi]
+  pub fn optimize(&self) -> Result<()> {
+    let conn = self.lock()?;
+    conn
+      .execute_batch("PRAGMA optimize")
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  // Writes a compacted copy of the database to `path` with `VACUUM INTO`. SQLite refuses to
+  // overwrite a non-empty file and to vacuum inside a transaction.
+  #[napi]
+  pub fn vacuum_into(&self, path: String) -> Result<()> {
+    if path.trim().is_empty() || path.contains('\0') {
+      return Err(napi::Error::from_reason("vacuumInto needs a file path"));
+    }
+    if path == ":memory:" {
+      return Err(napi::Error::from_reason(
+        "vacuumInto can't write to :memory:",
+      ));
+    }
+    let conn = self.lock()?;
+    if !conn.is_autocommit() {
+      return Err(napi::Error::from_reason(
+        "vacuumInto can't be used inside a transaction",
+      ));
+    }
+    conn
+      .execute("VACUUM INTO ?", [&path])
+      .map(|_| ())
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  // PRAGMA statements can't take bound parameters, so the name is validated and the
+  // argument inlined as a literal
+  #[napi]
+  pub fn pragma_rows(
+    &self,
+    env: Env,
+    name: String,
+    arg: Option<napi::Either<String, i64>>,
+  ) -> Result<Vec<JsObject>> {
+    if !is_valid_identifier(&name) {
+      return Err(napi::Error::from_reason(format!(
+        "Invalid pragma name: {}",
+        name
+      )));
+    }
+    let sql = match arg {
+      Some(arg) => format!("PRAGMA {}({})", name, sql_literal(&id_to_value(arg))),
+      None => format!("PRAGMA {}", name),
+    };
+
+    let conn = self.lock()?;
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let rows = stmt
+      .query_map([], |row| {
+        row_to_object(env, row, &column_names, &self.config)
+      })
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
 
-        let rows = stmt
-            .query_map([], |row| row_to_object(env, row, &column_names))
+    rows
+      .map(|row| row.map_err(|e| napi::Error::from_reason(e.to_string())))
+      .collect()
+  }
+
+  // Makes user input match literally inside a LIKE pattern, e.g. `%${escaped}%`
+  #[napi]
+  pub fn escape_like(input: String) -> String {
+    escape_like(&input)
+  }
+
+  // A TEXT column definition restricted to `values`, for CREATE TABLE; whereEnum reads the
+  // values back from the schema
+  #[napi]
+  pub fn enum_column(name: String, values: Vec<String>) -> Result<String> {
+    if values.is_empty() {
+      return Err(napi::Error::from_reason(
+        "enumColumn needs at least one value",
+      ));
+    }
+    Ok(enum_column_sql(&name, &values))
+  }
+
+  #[napi]
+  pub fn tables(&self) -> Result<Vec<String>> {
+    self.schema_names("table")
+  }
+
+  #[napi]
+  pub fn views(&self) -> Result<Vec<String>> {
+    self.schema_names("view")
+  }
+
+  fn schema_names(&self, kind: &str) -> Result<Vec<String>> {
+    let conn = self.lock()?;
+    let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = ? AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' ORDER BY name")
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
 
-        let mut results = Vec::new();
-        for row in rows {
-            results.push(row.map_err(|e| napi::Error::from_reason(e.to_string()))?);
+    let names = stmt
+      .query_map([kind], |row| row.get::<_, String>(0))
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    names
+      .map(|name| name.map_err(|e| napi::Error::from_reason(e.to_string())))
+      .collect()
+  }
+
+  #[napi]
+  pub fn begin(&self, behavior: Option<String>) -> Result<()> {
+    let begin = begin_sql(behavior.as_deref())?;
+    let conn = self.lock()?;
+    conn
+      .execute_batch(begin)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  #[napi]
+  pub fn commit(&self) -> Result<()> {
+    let conn = self.lock()?;
+    conn
+      .execute_batch("COMMIT")
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  #[napi]
+  pub fn rollback(&self) -> Result<()> {
+    let conn = self.lock()?;
+    conn
+      .execute_batch("ROLLBACK")
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  #[napi]
+  pub fn in_transaction(&self) -> Result<bool> {
+    Ok(!self.lock()?.is_autocommit())
+  }
+
+  #[napi]
+  pub fn transaction(
+    &self,
+    callback: JsFunction,
+    behavior: Option<String>,
+  ) -> Result<TransactionResult> {
+    let begin = begin_sql(behavior.as_deref())?;
+    let before = {
+      let conn = self.lock()?;
+      conn
+        .execute_batch(begin)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      total_changes(&conn).map_err(|e| napi::Error::from_reason(e.to_string()))?
+    };
+
+    let outcome = callback.call_without_args(None);
+
+    let conn = self.lock()?;
+    match outcome {
+      Ok(result) => {
+        let after = total_changes(&conn).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        if let Err(e) = conn.execute_batch("COMMIT") {
+          let _ = conn.execute_batch("ROLLBACK");
+          return Err(napi::Error::from_reason(e.to_string()));
         }
+        Ok(TransactionResult {
+          result,
+          changes: after - before,
+        })
+      }
+      Err(e) => {
+        let _ = conn.execute_batch("ROLLBACK");
+        Err(e)
+      }
+    }
+  }
+
+  // Runs `callback` with foreign key enforcement off, e.g. to bulk-load rows with circular
+  // references in any order, then restores the previous setting and reports what
+  // `foreign_key_check` finds. The pragma is a no-op inside a transaction, so this refuses to run
+  // in one; open transactions from the callback instead.
+  #[napi]
+  pub fn without_foreign_keys(
+    &self,
+    callback: JsFunction,
+    options: Option<ForeignKeyOptions>,
+  ) -> Result<ForeignKeyLoadResult> {
+    let check = options.and_then(|o| o.check).unwrap_or(true);
+    let enabled = {
+      let conn = self.lock()?;
+      if !conn.is_autocommit() {
+        return Err(napi::Error::from_reason(
+          "withoutForeignKeys can't be used inside a transaction",
+        ));
+      }
+      let enabled = conn
+        .query_row("PRAGMA foreign_keys", [], |row| row.get::<_, bool>(0))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      conn
+        .execute_batch("PRAGMA foreign_keys = OFF")
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      enabled
+    };
 
-        Ok(results)
+    let outcome = callback.call_without_args(None);
+
+    let conn = self.lock()?;
+    if enabled {
+      conn
+        .execute_batch("PRAGMA foreign_keys = ON")
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
     }
+    let result = outcome?;
 
-    #[napi]
-    pub fn table(&self, name: String) -> Result<Table> {
-        Ok(Table {
-            name,
-            conn: self.conn.clone(),
-            //relations: vec![],
+    let mut violations = Vec::new();
+    if check {
+      let mut stmt = conn
+        .prepare("PRAGMA foreign_key_check")
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      let rows = stmt
+        .query_map([], |row| {
+          Ok(ForeignKeyViolation {
+            table: row.get(0)?,
+            rowid: row.get(1)?,
+            parent: row.get(2)?,
+            fkid: row.get(3)?,
+          })
         })
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      for row in rows {
+        violations.push(row.map_err(|e| napi::Error::from_reason(e.to_string()))?);
+      }
     }
+
+    Ok(ForeignKeyLoadResult { result, violations })
+  }
+
+  #[napi]
+  pub fn migrate(&self, this: This<JsObject>, migrations: Vec<JsObject>) -> Result<Vec<String>> {
+    let mut applied = {
+      let conn = self.lock()?;
+      conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS _migrations (name TEXT PRIMARY KEY, applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+            )
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+      let mut stmt = conn
+        .prepare("SELECT name FROM _migrations")
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      names
+        .map(|name| name.map_err(|e| napi::Error::from_reason(e.to_string())))
+        .collect::<Result<HashSet<_>>>()?
+    };
+
+    let mut ran = Vec::new();
+    for migration in migrations {
+      let name: String = migration.get_named_property("name")?;
+      if applied.contains(&name) {
+        continue;
+      }
+      let up: JsFunction = migration.get_named_property("up")?;
+
+      self
+        .lock()?
+        .execute_batch("BEGIN IMMEDIATE")
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+      let outcome = up.call(None, &[&this]).and_then(|_| {
+        let conn = self.lock()?;
+        conn
+          .execute("INSERT INTO _migrations (name) VALUES (?)", [&name])
+          .and_then(|_| conn.execute_batch("COMMIT"))
+          .map_err(|e| napi::Error::from_reason(e.to_string()))
+      });
+
+      if let Err(e) = outcome {
+        let _ = self.conn.lock().unwrap().execute_batch("ROLLBACK");
+        return Err(napi::Error::from_reason(format!(
+          "Migration '{}' failed: {}",
+          name, e.reason
+        )));
+      }
+
+      applied.insert(name.clone());
+      ran.push(name);
+    }
+
+    Ok(ran)
+  }
+
+  #[napi]
+  pub fn rename_table(&self, old_name: String, new_name: String) -> Result<()> {
+    let conn = self.lock()?;
+    conn
+      .execute_batch(&format!(
+        "ALTER TABLE {} RENAME TO {}",
+        quote_identifier(&old_name),
+        quote_identifier(&new_name)
+      ))
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    // Keep registered relations pointing at the renamed table
+    let mut relations = self.relations.lock().unwrap();
+    if let Some(entry) = relations.remove(&old_name) {
+      relations.insert(new_name.clone(), entry);
+    }
+    for relation in relations.values_mut().flatten() {
+      if relation.table == old_name {
+        relation.table = new_name.clone();
+      }
+    }
+    Ok(())
+  }
+
+  #[napi]
+  pub fn with_cte(&self, name: String, sql: String) -> Database {
+    let mut ctes = self.ctes.clone();
+    ctes.retain(|(existing, _)| *existing != name);
+    ctes.push((name, sql));
+    Database {
+      ctes,
+      borrowed: true,
+      ..self.share()
+    }
+  }
+
+  // Loads `rows` into a new TEMP table for joins and subqueries, as a scalable alternative
+  // to huge IN lists. The table is dropped once the returned handle (and every query
+  // built from it) has been garbage collected.
+  #[napi]
+  pub fn with_temp_rows(&self, env: Env, name: String, rows: Vec<JsObject>) -> Result<Table> {
+    if !is_valid_identifier(&name) {
+      return Err(napi::Error::from_reason(format!(
+        "Invalid temp table name: {}",
+        name
+      )));
+    }
+    let Some(first) = rows.first() else {
+      return Err(napi::Error::from_reason(
+        "withTempRows needs at least one row",
+      ));
+    };
+
+    let mut columns: Vec<String> = js_object_to_hashmap(&env, first, None)?
+      .into_keys()
+      .collect();
+    columns.sort();
+    if columns.is_empty() {
+      return Err(napi::Error::from_reason(
+        "withTempRows rows need at least one column",
+      ));
+    }
+    let mut values = Vec::with_capacity(rows.len() * columns.len());
+    for row in &rows {
+      let mut map = js_object_to_hashmap(&env, row, None)?;
+      if map.len() != columns.len() || !columns.iter().all(|c| map.contains_key(c)) {
+        return Err(napi::Error::from_reason(
+          "All rows passed to withTempRows must have the same columns",
+        ));
+      }
+      for column in &columns {
+        let value = map.remove(column).expect("checked above");
+        values.push(js_unknown_to_bind_value(value, &self.config)?);
+      }
+    }
+
+    let column_list = columns
+      .iter()
+      .map(|c| quote_identifier(c))
+      .collect::<Vec<_>>()
+      .join(", ");
+    let row_placeholders = format!("({})", vec!["?"; columns.len()].join(", "));
+
+    let mut conn = self.lock()?;
+    let max_variables = conn
+      .limit(rusqlite::limits::Limit::SQLITE_LIMIT_VARIABLE_NUMBER)
+      .max(1) as usize;
+    let rows_per_chunk = (max_variables / columns.len()).max(1);
+
+    let tx = conn
+      .savepoint()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    tx.execute_batch(&format!(
+      "CREATE TEMP TABLE {} ({})",
+      quote_identifier(&name),
+      column_list
+    ))
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    for chunk in values.chunks(rows_per_chunk * columns.len()) {
+      let sql = format!(
+        "INSERT INTO temp.{} ({}) VALUES {}",
+        quote_identifier(&name),
+        column_list,
+        vec![row_placeholders.as_str(); chunk.len() / columns.len()].join(", ")
+      );
+      tx.execute(&sql, rusqlite::params_from_iter(chunk))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    }
+    tx.commit()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    drop(conn);
+
+    let mut table = self.table(name.clone())?;
+    table.temp = Some(Arc::new(TempTable {
+      conn: self.conn.clone(),
+      name,
+    }));
+    Ok(table)
+  }
+
+  #[napi]
+  pub fn table(&self, name: String) -> Result<Table> {
+    self.check_open()?;
+    // Table names are spliced into every statement the builder writes
+    if !is_valid_qualified_identifier(&name) {
+      return Err(napi::Error::from_reason(format!(
+        "Invalid table name '{}': use letters, digits and _ (or schema.table)",
+        name
+      )));
+    }
+    Ok(Table {
+      name,
+      conn: self.conn.clone(),
+      interrupt: self.interrupt.clone(),
+      closed: self.closed.clone(),
+      config: self.config.clone(),
+      relations: self.relations.clone(),
+      ctes: self.ctes.clone(),
+      cache: self.cache.clone(),
+      schema: self.schema.clone(),
+      alias: None,
+      temp: None,
+    })
+  }
 }
 
+// Mirrors rusqlite's TransactionBehavior; the BEGIN is issued by hand because the
+// transaction has to outlive the connection lock while JS callbacks run
+fn begin_sql(behavior: Option<&str>) -> Result<&'static str> {
+  match behavior.map(str::to_lowercase).as_deref() {
+    None | Some("deferred") => Ok("BEGIN DEFERRED"),
+    Some("immediate") => Ok("BEGIN IMMEDIATE"),
+    Some("exclusive") => Ok("BEGIN EXCLUSIVE"),
+    Some(other) => Err(napi::Error::from_reason(format!(
+      "Unknown transaction behavior '{}', expected deferred, immediate or exclusive",
+      other
+    ))),
+  }
+}
+
+// The encoding is fixed once the first table is created, so it can only be chosen for a new database
+fn set_encoding(conn: &Connection, encoding: &str) -> Result<()> {
+  let encoding = match encoding.to_uppercase().as_str() {
+    "UTF-8" | "UTF8" => "UTF-8",
+    "UTF-16" | "UTF16" => "UTF-16",
+    "UTF-16LE" | "UTF16LE" => "UTF-16le",
+    "UTF-16BE" | "UTF16BE" => "UTF-16be",
+    _ => {
+      return Err(napi::Error::from_reason(format!(
+        "Unknown encoding '{}', expected UTF-8, UTF-16, UTF-16le or UTF-16be",
+        encoding
+      )))
+    }
+  };
+
+  let current: String = conn
+    .query_row("PRAGMA encoding", [], |row| row.get(0))
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+  // Plain UTF-16 means native byte order, so accept either variant as a match
+  if current.eq_ignore_ascii_case(encoding)
+    || (encoding == "UTF-16" && current.starts_with("UTF-16"))
+  {
+    return Ok(());
+  }
+
+  let objects: i64 = conn
+    .query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get(0))
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+  if objects > 0 {
+    return Err(napi::Error::from_reason(format!(
+      "Cannot change encoding to {} because the database already uses {} and contains tables",
+      encoding, current
+    )));
+  }
+
+  conn
+    .execute_batch(&format!("PRAGMA encoding = '{}'", encoding))
+    .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+fn storage_class(value: &Value) -> &'static str {
+  match value {
+    Value::Null => "NULL",
+    Value::Integer(_) => "INTEGER",
+    Value::Real(_) => "REAL",
+    Value::Text(_) => "TEXT",
+    Value::Blob(_) => "BLOB",
+  }
+}
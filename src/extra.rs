@@ -1,111 +1,1039 @@
-use napi::{Env, JsObject, JsUnknown, Result, ValueType};
-use rusqlite::{Row};
+use napi::bindgen_prelude::{BigInt, ToNapiValue};
+use napi::{
+  Env, JsBigInt, JsBufferValue, JsFunction, JsObject, JsString, JsStringUtf8, JsUnknown, NapiValue,
+  Result, ValueType,
+};
+use rusqlite::types::{ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, Row, Statement};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
 
-fn id_value_to_string(val: &rusqlite::types::Value) -> String {
+#[derive(Clone, Copy, Default)]
+pub enum DateFormat {
+  #[default]
+  Iso,
+  Unix,
+  UnixMs,
+}
+
+impl DateFormat {
+  pub fn parse(name: &str) -> Result<Self> {
+    match name.to_lowercase().as_str() {
+      "iso" => Ok(DateFormat::Iso),
+      "unix" => Ok(DateFormat::Unix),
+      "unixms" => Ok(DateFormat::UnixMs),
+      other => Err(napi::Error::from_reason(format!(
+        "Unknown date format '{}', expected one of iso, unix, unixms",
+        other
+      ))),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Default)]
+pub enum NonFinite {
+  #[default]
+  Error,
+  Null,
+}
+
+impl NonFinite {
+  pub fn parse(name: &str) -> Result<Self> {
+    match name.to_lowercase().as_str() {
+      "error" => Ok(NonFinite::Error),
+      "null" => Ok(NonFinite::Null),
+      other => Err(napi::Error::from_reason(format!(
+        "Unknown nonFinite mode '{}', expected error or null",
+        other
+      ))),
+    }
+  }
+}
+
+// Operator for `where(column, value)` and object equalities. `IS` is the null-safe spelling of
+// `=`, matching the same rows for non-null values while staying true for NULL = NULL.
+#[derive(Clone, Copy, Default)]
+pub enum DefaultOperator {
+  #[default]
+  Equals,
+  Is,
+}
+
+impl DefaultOperator {
+  pub fn parse(name: &str) -> Result<Self> {
+    match name.to_lowercase().as_str() {
+      "=" => Ok(DefaultOperator::Equals),
+      "is" => Ok(DefaultOperator::Is),
+      other => Err(napi::Error::from_reason(format!(
+        "Unknown default operator '{}', expected = or is",
+        other
+      ))),
+    }
+  }
+
+  pub fn sql(self) -> &'static str {
+    match self {
+      DefaultOperator::Equals => "=",
+      DefaultOperator::Is => "IS",
+    }
+  }
+}
+
+#[derive(Clone, Default)]
+pub struct Config {
+  pub date_format: DateFormat,
+  pub non_finite: NonFinite,
+  // Read INTEGER and REAL columns back as strings
+  pub numbers_as_strings: bool,
+  // Read INTEGER columns and counts back as BigInt
+  pub bigint: bool,
+  pub default_operator: DefaultOperator,
+}
+
+// NaN and ±Infinity have no faithful SQLite representation (NaN binds as NULL,
+// Infinity as a REAL that no longer round-trips), so they're rejected or nulled
+pub fn real_value(num: f64, config: &Config) -> Result<rusqlite::types::Value> {
+  if num.is_finite() {
+    return Ok(rusqlite::types::Value::Real(num));
+  }
+  match config.non_finite {
+        NonFinite::Null => Ok(rusqlite::types::Value::Null),
+        NonFinite::Error => Err(napi::Error::from_reason(format!(
+            "Cannot store non-finite number {} (set the nonFinite option to 'null' to store NULL instead)",
+            if num.is_nan() { "NaN" } else if num > 0.0 { "Infinity" } else { "-Infinity" }
+        ))),
+    }
+}
+
+pub fn supports_update_delete_limit(conn: &Connection) -> bool {
+  conn
+    .query_row(
+      "SELECT sqlite_compileoption_used('ENABLE_UPDATE_DELETE_LIMIT')",
+      [],
+      |row| row.get::<_, bool>(0),
+    )
+    .unwrap_or(false)
+}
+
+pub fn quote_identifier(name: &str) -> String {
+  format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Table names may be `schema.table`; each part is quoted on its own, as `"main.users"` would be
+// one identifier
+pub fn quote_table_name(name: &str) -> String {
+  name
+    .split('.')
+    .map(quote_identifier)
+    .collect::<Vec<_>>()
+    .join(".")
+}
+
+// Splits `schema.table` into a quoted `"schema".` prefix and the bare table name, for the PRAGMAs
+// and sqlite_master lookups that take the schema separately
+pub fn split_table_name(name: &str) -> (String, &str) {
+  match name.split_once('.') {
+    Some((schema, table)) => (format!("{}.", quote_identifier(schema)), table),
+    None => (String::new(), name),
+  }
+}
+
+// `"name" TEXT CHECK ("name" IN ('a', 'b'))`, the definition behind Database.enumColumn
+pub fn enum_column_sql(name: &str, values: &[String]) -> String {
+  let column = quote_identifier(name);
+  let literals = values
+    .iter()
+    .map(|v| sql_literal(&rusqlite::types::Value::Text(v.clone())))
+    .collect::<Vec<_>>()
+    .join(", ");
+  format!("{column} TEXT CHECK ({column} IN ({literals}))")
+}
+
+// Reads an enum_column_sql CHECK back out of a stored CREATE TABLE statement
+pub fn enum_values(create_sql: &str, name: &str) -> Option<Vec<String>> {
+  let marker = format!("CHECK ({} IN (", quote_identifier(name));
+  let mut rest = &create_sql[create_sql.find(&marker)? + marker.len()..];
+  let mut values = Vec::new();
+  loop {
+    rest = rest.strip_prefix('\'')?;
+    let mut value = String::new();
+    loop {
+      let end = rest.find('\'')?;
+      value.push_str(&rest[..end]);
+      rest = &rest[end + 1..];
+      match rest.strip_prefix('\'') {
+        Some(after) => {
+          value.push('\'');
+          rest = after;
+        }
+        None => break,
+      }
+    }
+    values.push(value);
+    if rest.starts_with(')') {
+      return Some(values);
+    }
+    rest = rest.strip_prefix(", ")?;
+  }
+}
+
+// Column type specs are spliced into DDL, so only allow a type name followed by
+// plain constraint words, numbers and quoted literals
+pub fn validate_type_spec(spec: &str) -> Result<()> {
+  let spec = spec.trim();
+  let valid = spec.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+    && spec
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || " _(),.'+-".contains(c))
+    && !spec.contains("--")
+    && spec.matches('\'').count().is_multiple_of(2)
+    && spec.matches('(').count() == spec.matches(')').count();
+  if valid {
+    Ok(())
+  } else {
+    Err(napi::Error::from_reason(format!(
+      "Invalid column type spec '{}'",
+      spec
+    )))
+  }
+}
+
+pub fn is_valid_identifier(name: &str) -> bool {
+  name
+    .chars()
+    .next()
+    .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// A plain identifier or one `schema.name` pair, for table and column names that are spliced into
+// SQL as written
+pub fn is_valid_qualified_identifier(name: &str) -> bool {
+  let mut parts = name.split('.');
+  parts.next().is_some_and(is_valid_identifier)
+    && parts.next().is_none_or(is_valid_identifier)
+    && parts.next().is_none()
+}
+
+// Escapes LIKE wildcards with a backslash, for patterns matched with `ESCAPE '\'`
+pub fn escape_like(input: &str) -> String {
+  let mut escaped = String::with_capacity(input.len());
+  for c in input.chars() {
+    if matches!(c, '%' | '_' | '\\') {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
+pub fn id_to_value(id: napi::Either<String, i64>) -> rusqlite::types::Value {
+  match id {
+    napi::Either::A(s) => rusqlite::types::Value::Text(s),
+    napi::Either::B(i) => rusqlite::types::Value::Integer(i),
+  }
+}
+
+pub fn value_key(val: &rusqlite::types::Value) -> String {
+  match val {
+    rusqlite::types::Value::Text(s) => s.clone(),
+    rusqlite::types::Value::Integer(i) => i.to_string(),
+    rusqlite::types::Value::Real(f) => f.to_string(),
+    rusqlite::types::Value::Blob(b) => format!("{:?}", b),
+    rusqlite::types::Value::Null => "".to_string(),
+  }
+}
+
+pub fn describe_value(val: &rusqlite::types::Value) -> String {
+  match val {
+    rusqlite::types::Value::Null => "NULL".to_string(),
+    rusqlite::types::Value::Integer(i) => i.to_string(),
+    rusqlite::types::Value::Real(f) => f.to_string(),
+    rusqlite::types::Value::Text(s) => format!("'{}'", s),
+    rusqlite::types::Value::Blob(b) => format!("<{} bytes>", b.len()),
+  }
+}
+
+pub fn sql_literal(val: &rusqlite::types::Value) -> String {
+  match val {
+    rusqlite::types::Value::Null => "NULL".to_string(),
+    rusqlite::types::Value::Integer(i) => i.to_string(),
+    rusqlite::types::Value::Real(f) => format!("{:?}", f),
+    rusqlite::types::Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+    rusqlite::types::Value::Blob(b) => {
+      format!(
+        "X'{}'",
+        b.iter()
+          .map(|byte| format!("{:02X}", byte))
+          .collect::<String>()
+      )
+    }
+  }
+}
+
+// Substitutes `?` placeholders (outside of quoted strings and identifiers) with literals,
+// for DDL such as partial index predicates where SQLite doesn't allow bound parameters
+pub fn inline_params(sql: &str, params: &[rusqlite::types::Value]) -> Result<String> {
+  let mut out = String::with_capacity(sql.len());
+  let mut params = params.iter();
+  let mut quote: Option<char> = None;
+  for c in sql.chars() {
+    match (quote, c) {
+      (Some(q), c) if c == q => quote = None,
+      (None, '\'' | '"') => quote = Some(c),
+      (None, '?') => {
+        let value = params.next().ok_or_else(|| {
+          napi::Error::from_reason("More placeholders than parameters in condition")
+        })?;
+        out.push_str(&sql_literal(value));
+        continue;
+      }
+      _ => {}
+    }
+    out.push(c);
+  }
+  if params.next().is_some() {
+    return Err(napi::Error::from_reason(
+      "More parameters than placeholders in condition",
+    ));
+  }
+  Ok(out)
+}
+
+#[derive(Clone, Copy)]
+pub enum BlobEncoding {
+  Base64,
+  Hex,
+  Array,
+}
+
+impl BlobEncoding {
+  pub fn parse(name: &str) -> Result<Self> {
+    match name.to_lowercase().as_str() {
+      "base64" => Ok(BlobEncoding::Base64),
+      "hex" => Ok(BlobEncoding::Hex),
+      "array" => Ok(BlobEncoding::Array),
+      other => Err(napi::Error::from_reason(format!(
+        "Unknown blob encoding '{}', expected one of base64, hex, array",
+        other
+      ))),
+    }
+  }
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let n = (chunk[0] as u32) << 16
+      | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+      | *chunk.get(2).unwrap_or(&0) as u32;
+    for i in 0..4 {
+      if i <= chunk.len() {
+        out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  out
+}
+
+fn json_string(s: &str, out: &mut String) {
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+pub fn json_value(val: &rusqlite::types::Value, blobs: BlobEncoding, out: &mut String) {
+  match val {
+    rusqlite::types::Value::Null => out.push_str("null"),
+    rusqlite::types::Value::Integer(i) => out.push_str(&i.to_string()),
+    rusqlite::types::Value::Real(f) if f.is_finite() => out.push_str(&f.to_string()),
+    rusqlite::types::Value::Real(_) => out.push_str("null"),
+    rusqlite::types::Value::Text(s) => json_string(s, out),
+    rusqlite::types::Value::Blob(b) => match blobs {
+      BlobEncoding::Base64 => json_string(&base64_encode(b), out),
+      BlobEncoding::Hex => json_string(
+        &b.iter()
+          .map(|byte| format!("{:02x}", byte))
+          .collect::<String>(),
+        out,
+      ),
+      BlobEncoding::Array => {
+        out.push('[');
+        out.push_str(
+          &b.iter()
+            .map(|byte| byte.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        );
+        out.push(']');
+      }
+    },
+  }
+}
+
+// One CSV field: quoted when it holds a separator, quote or line break; NULL is empty
+// and blobs are base64
+pub fn csv_field(val: &rusqlite::types::Value, out: &mut String) {
+  let text = match val {
+    rusqlite::types::Value::Null => return,
+    rusqlite::types::Value::Integer(i) => return out.push_str(&i.to_string()),
+    rusqlite::types::Value::Real(f) => return out.push_str(&f.to_string()),
+    rusqlite::types::Value::Text(s) => s.as_str(),
+    rusqlite::types::Value::Blob(b) => return out.push_str(&base64_encode(b)),
+  };
+  if text.contains([',', '"', '\n', '\r']) {
+    out.push('"');
+    out.push_str(&text.replace('"', "\"\""));
+    out.push('"');
+  } else {
+    out.push_str(text);
+  }
+}
+
+pub fn values_equal(a: &rusqlite::types::Value, b: &rusqlite::types::Value) -> bool {
+  use rusqlite::types::Value;
+  match (a, b) {
+    (Value::Integer(x), Value::Real(y)) | (Value::Real(y), Value::Integer(x)) => *x as f64 == *y,
+    _ => a == b,
+  }
+}
+
+pub fn total_changes(conn: &Connection) -> rusqlite::Result<i64> {
+  conn.query_row("SELECT total_changes()", [], |row| row.get(0))
+}
+
+// `Database::close` leaves an in-memory placeholder behind the lock for the handles still
+// sharing it, so the flag is checked to fail their calls instead of running on the placeholder
+pub fn lock_connection<'a>(
+  conn: &'a Mutex<Connection>,
+  closed: &AtomicBool,
+) -> Result<MutexGuard<'a, Connection>> {
+  let conn = conn
+    .lock()
+    .map_err(|e| napi::Error::from_reason(format!("Lock poisoned: {}", e)))?;
+  if closed.load(Ordering::SeqCst) {
+    return Err(napi::Error::from_reason("The database is closed"));
+  }
+  Ok(conn)
+}
+
+// COUNT results are exact i64s, which only survive the trip to JS past 2^53 as a BigInt
+pub fn count_to_js(count: i64, config: &Config) -> napi::Either<i64, BigInt> {
+  if config.bigint {
+    napi::Either::B(BigInt::from(count))
+  } else {
+    napi::Either::A(count)
+  }
+}
+
+pub fn value_to_js(env: Env, val: rusqlite::types::Value) -> Result<JsUnknown> {
+  let raw = unsafe {
     match val {
-        rusqlite::types::Value::Text(s) => s.clone(),
-        rusqlite::types::Value::Integer(i) => i.to_string(),
-        _ => "".to_string(),
+      rusqlite::types::Value::Integer(v) => ToNapiValue::to_napi_value(env.raw(), v)?,
+      rusqlite::types::Value::Real(v) => ToNapiValue::to_napi_value(env.raw(), v)?,
+      rusqlite::types::Value::Text(v) => ToNapiValue::to_napi_value(env.raw(), v)?,
+      rusqlite::types::Value::Blob(v) => ToNapiValue::to_napi_value(env.raw(), v)?,
+      rusqlite::types::Value::Null => return env.get_undefined().map(|v| v.into_unknown()),
     }
+  };
+  Ok(unsafe { JsUnknown::from_raw_unchecked(env.raw(), raw) })
 }
 
-pub fn row_to_object(env: Env, row: &Row, columns: &[String]) -> rusqlite::Result<JsObject> {
-    let mut obj = env.create_object().unwrap();
+// A column value read from the database, honouring the bigint and numbersAsStrings options
+pub fn column_to_js(env: Env, val: rusqlite::types::Value, config: &Config) -> Result<JsUnknown> {
+  match val {
+    rusqlite::types::Value::Integer(v) if config.bigint => {
+      env.create_bigint_from_i64(v)?.into_unknown()
+    }
+    rusqlite::types::Value::Integer(v) if config.numbers_as_strings => {
+      env.create_string(&v.to_string()).map(|s| s.into_unknown())
+    }
+    rusqlite::types::Value::Real(v) if config.numbers_as_strings => {
+      env.create_string(&v.to_string()).map(|s| s.into_unknown())
+    }
+    val => value_to_js(env, val),
+  }
+}
 
-    for (i, col) in columns.iter().enumerate() {
-        let val: rusqlite::types::Value = row.get(i)?;
-        match val {
-            rusqlite::types::Value::Integer(v) => {
-                obj.set(col.as_str(), v).unwrap();
-            }
-            rusqlite::types::Value::Real(v) => {
-                obj.set(col.as_str(), v).unwrap();
-            }
-            rusqlite::types::Value::Text(v) => {
-                obj.set(col.as_str(), v).unwrap();
-            }
-            rusqlite::types::Value::Blob(v) => {
-                obj.set(col.as_str(), v).unwrap();
-            }
-            rusqlite::types::Value::Null => {
-                obj.set(col.as_str(), env.get_undefined().unwrap()).unwrap();
-            }
+// Bound values as reported back to JS for logging; NULL is `null` here rather than `undefined`
+pub fn bindings_to_js(env: Env, values: &[rusqlite::types::Value]) -> Result<Vec<JsUnknown>> {
+  values
+    .iter()
+    .map(|value| match value {
+      rusqlite::types::Value::Null => env.get_null().map(|v| v.into_unknown()),
+      value => value_to_js(env, value.clone()),
+    })
+    .collect()
+}
+
+pub fn row_to_object(
+  env: Env,
+  row: &Row,
+  columns: &[String],
+  config: &Config,
+) -> rusqlite::Result<JsObject> {
+  let mut obj = env.create_object().unwrap();
+
+  for (i, col) in columns.iter().enumerate() {
+    let val: rusqlite::types::Value = row.get(i)?;
+    obj
+      .set(col.as_str(), column_to_js(env, val, config).unwrap())
+      .unwrap();
+  }
+
+  Ok(obj)
+}
+
+// Column names as JS strings, built once per statement and reused as keys for every row
+pub fn column_keys(env: Env, columns: &[String]) -> Result<Vec<JsString>> {
+  columns
+    .iter()
+    .map(|column| env.create_string(column))
+    .collect()
+}
+
+pub fn row_to_keyed_object(
+  env: Env,
+  row: &Row,
+  keys: &[JsString],
+  config: &Config,
+) -> rusqlite::Result<JsObject> {
+  let mut obj = env.create_object().unwrap();
+
+  for (i, key) in keys.iter().enumerate() {
+    let val: rusqlite::types::Value = row.get(i)?;
+    obj
+      .set_property(*key, column_to_js(env, val, config).unwrap())
+      .unwrap();
+  }
+
+  Ok(obj)
+}
+
+pub fn row_to_array(
+  env: Env,
+  row: &Row,
+  column_count: usize,
+  config: &Config,
+) -> rusqlite::Result<JsObject> {
+  let mut arr = env.create_array_with_length(column_count).unwrap();
+
+  for i in 0..column_count {
+    let val: rusqlite::types::Value = row.get(i)?;
+    arr
+      .set_element(i as u32, column_to_js(env, val, config).unwrap())
+      .unwrap();
+  }
+
+  Ok(arr)
+}
+
+pub fn query_to_arrays(
+  env: Env,
+  conn: &Connection,
+  sql: &str,
+  params: SqlParams,
+  config: &Config,
+) -> Result<JsObject> {
+  let mut stmt = conn
+    .prepare(sql)
+    .map_err(|e| napi::Error::from_reason(format!("Prepare failed: {}", e)))?;
+
+  let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+  let column_count = column_names.len();
+
+  let rows = collect_rows(&mut stmt, params, |row| {
+    row_to_array(env, row, column_count, config)
+  })?;
+
+  let mut js_rows = env.create_empty_array()?;
+  for (i, row) in rows.into_iter().enumerate() {
+    js_rows.set_element(i as u32, row)?;
+  }
+
+  let mut result = env.create_object()?;
+  result.set("columns", column_names)?;
+  result.set("rows", js_rows)?;
+  Ok(result)
+}
+
+pub fn js_object_to_hashmap(
+  env: &Env,
+  obj: &JsObject,
+  json_columns: Option<&[String]>,
+) -> Result<HashMap<String, JsUnknown>> {
+  let property_names = obj.get_property_names()?;
+  let length = property_names.get_array_length()?;
+  let mut map = HashMap::new();
+
+  let global = env.get_global()?;
+  let json = global.get_named_property::<JsObject>("JSON")?;
+  let stringify = json.get_named_property::<napi::JsFunction>("stringify")?;
+
+  for i in 0..length {
+    let key = property_names
+      .get_element::<JsUnknown>(i)?
+      .coerce_to_string()?
+      .into_utf8()?
+      .as_str()?
+      .to_owned();
+
+    let Some(value) = obj.get::<_, JsUnknown>(&key)? else {
+      continue;
+    };
+
+    match value.get_type()? {
+      ValueType::Object if value.is_date()? || value.is_buffer()? => {
+        map.insert(key, value);
+      }
+      ValueType::Object => {
+        if json_columns.is_some_and(|cols| !cols.contains(&key)) {
+          return Err(napi::Error::from_reason(format!(
+            "Column {} received an object but is not declared as a JSON column",
+            key
+          )));
         }
+        let serialized = stringify
+          .call(None, &[value])?
+          .coerce_to_string()?
+          .into_unknown();
+        map.insert(key, serialized);
+      }
+      ValueType::Undefined | ValueType::Null => {
+        continue;
+      }
+      _ => {
+        map.insert(key, value);
+      }
+    }
+  }
+
+  Ok(map)
+}
+
+pub fn js_unknown_to_rusqlite_value(
+  val: JsUnknown,
+  config: &Config,
+) -> napi::Result<rusqlite::types::Value> {
+  let val_type = val.get_type()?;
+
+  match val_type {
+    ValueType::Null | ValueType::Undefined => Ok(rusqlite::types::Value::Null),
+
+    ValueType::Boolean => {
+      let bool_val = val.coerce_to_bool()?.get_value()?;
+      Ok(rusqlite::types::Value::Integer(if bool_val {
+        1
+      } else {
+        0
+      }))
+    }
+
+    ValueType::Number => {
+      let num_val = val.coerce_to_number()?.get_double()?;
+      if !num_val.is_finite() {
+        real_value(num_val, config)
+      } else if num_val.fract() == 0.0 {
+        // Bilangan bulat
+        Ok(rusqlite::types::Value::Integer(num_val as i64))
+      } else {
+        // Bilangan desimal
+        Ok(rusqlite::types::Value::Real(num_val))
+      }
+    }
+
+    ValueType::String => {
+      let str_val = val.coerce_to_string()?.into_utf8()?;
+      Ok(rusqlite::types::Value::Text(str_val.as_str()?.to_owned()))
+    }
+
+    ValueType::BigInt => {
+      let (int_val, lossless) = unsafe { val.cast::<JsBigInt>() }.get_i64()?;
+      if !lossless {
+        return Err(napi::Error::from_reason(
+          "BigInt value is out of range for a 64-bit integer",
+        ));
+      }
+      Ok(rusqlite::types::Value::Integer(int_val))
+    }
+
+    ValueType::Object if val.is_date()? => date_to_rusqlite_value(val, config.date_format),
+
+    ValueType::Object if val.is_buffer()? => {
+      let buffer = unsafe { val.cast::<napi::JsBuffer>() }.into_value()?;
+      Ok(rusqlite::types::Value::Blob(buffer.to_vec()))
+    }
+
+    _ => Ok(rusqlite::types::Value::Null),
+  }
+}
+
+// Bind parameter that borrows large strings and buffers from the JS side instead of
+// copying them into an owned `Value` first
+pub enum BindValue {
+  Value(rusqlite::types::Value),
+  Text(JsStringUtf8),
+  Blob(JsBufferValue),
+}
+
+impl BindValue {
+  pub fn to_value(&self) -> rusqlite::types::Value {
+    match self {
+      BindValue::Value(v) => v.clone(),
+      BindValue::Text(s) => {
+        rusqlite::types::Value::Text(String::from_utf8_lossy(s.as_slice()).into_owned())
+      }
+      BindValue::Blob(b) => rusqlite::types::Value::Blob(b.to_vec()),
     }
+  }
+}
+
+impl ToSql for BindValue {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(match self {
+      BindValue::Value(v) => ToSqlOutput::Borrowed(v.into()),
+      BindValue::Text(s) => ToSqlOutput::Borrowed(ValueRef::Text(s.as_slice())),
+      BindValue::Blob(b) => ToSqlOutput::Borrowed(ValueRef::Blob(b)),
+    })
+  }
+}
 
-    Ok(obj)
+pub fn js_unknown_to_bind_value(val: JsUnknown, config: &Config) -> napi::Result<BindValue> {
+  match val.get_type()? {
+    ValueType::String => Ok(BindValue::Text(val.coerce_to_string()?.into_utf8()?)),
+    ValueType::Object if val.is_buffer()? => Ok(BindValue::Blob(
+      unsafe { val.cast::<napi::JsBuffer>() }.into_value()?,
+    )),
+    _ => Ok(BindValue::Value(js_unknown_to_rusqlite_value(val, config)?)),
+  }
 }
 
-pub fn js_object_to_hashmap(env: &Env, obj: &JsObject) -> Result<HashMap<String, JsUnknown>> {
-    let property_names = obj.get_property_names()?;
-    let length = property_names.get_array_length()?;
-    let mut map = HashMap::new();
+// Raw SQL parameters: a JS array binds positionally, a plain object binds by name
+pub type JsParams = napi::Either<Vec<JsUnknown>, JsObject>;
 
-    let global = env.get_global()?;
-    let json = global.get_named_property::<JsObject>("JSON")?;
-    let stringify = json.get_named_property::<napi::JsFunction>("stringify")?;
+pub enum SqlParams {
+  Positional(Vec<BindValue>),
+  Named(HashMap<String, BindValue>),
+}
+
+// SqlParams copied out of JS memory, so they can move to a worker thread
+pub enum OwnedParams {
+  Positional(Vec<rusqlite::types::Value>),
+  Named(HashMap<String, rusqlite::types::Value>),
+}
+
+impl From<OwnedParams> for SqlParams {
+  fn from(params: OwnedParams) -> Self {
+    match params {
+      OwnedParams::Positional(values) => {
+        SqlParams::Positional(values.into_iter().map(BindValue::Value).collect())
+      }
+      OwnedParams::Named(values) => SqlParams::Named(
+        values
+          .into_iter()
+          .map(|(name, v)| (name, BindValue::Value(v)))
+          .collect(),
+      ),
+    }
+  }
+}
 
-    for i in 0..length {
-        let key = property_names
+impl SqlParams {
+  pub fn into_owned(self) -> OwnedParams {
+    match self {
+      SqlParams::Positional(values) => {
+        OwnedParams::Positional(values.iter().map(BindValue::to_value).collect())
+      }
+      SqlParams::Named(values) => OwnedParams::Named(
+        values
+          .iter()
+          .map(|(name, v)| (name.clone(), v.to_value()))
+          .collect(),
+      ),
+    }
+  }
+
+  pub fn from_js(params: Option<JsParams>, config: &Config) -> Result<Self> {
+    match params {
+      None => Ok(SqlParams::Positional(Vec::new())),
+      Some(napi::Either::A(values)) => values
+        .into_iter()
+        .map(|v| js_unknown_to_bind_value(v, config))
+        .collect::<Result<Vec<_>>>()
+        .map(SqlParams::Positional),
+      Some(napi::Either::B(obj)) => {
+        let names = obj.get_property_names()?;
+        let mut map = HashMap::new();
+        for i in 0..names.get_array_length()? {
+          let key = names
             .get_element::<JsUnknown>(i)?
             .coerce_to_string()?
             .into_utf8()?
-            .as_str()?
-            .to_owned();
-
-        let Some(value) = obj.get::<_, JsUnknown>(&key)? else {
-            continue;
-        };
-
-        match value.get_type()? {
-            ValueType::Object => {
-                let serialized = stringify
-                    .call(None, &[value])?
-                    .coerce_to_string()?
-                    .into_unknown();
-                map.insert(key, serialized);
-            }
-            ValueType::Undefined | ValueType::Null => {
-                continue;
-            }
+            .into_owned()?;
+          let value = obj.get_named_property::<JsUnknown>(&key)?;
+          map.insert(key, js_unknown_to_bind_value(value, config)?);
+        }
+        Ok(SqlParams::Named(map))
+      }
+    }
+  }
+
+  // Identifies the bound values for the query cache; literals keep 1 and '1' apart
+  pub fn cache_key(&self) -> String {
+    match self {
+      SqlParams::Positional(values) => values
+        .iter()
+        .map(|v| sql_literal(&v.to_value()))
+        .collect::<Vec<_>>()
+        .join(","),
+      SqlParams::Named(values) => {
+        let mut pairs = values
+          .iter()
+          .map(|(name, v)| format!("{}={}", name, sql_literal(&v.to_value())))
+          .collect::<Vec<_>>();
+        pairs.sort();
+        pairs.join(",")
+      }
+    }
+  }
+
+  pub fn bind(self, stmt: &mut Statement) -> Result<()> {
+    let count = stmt.parameter_count();
+    match self {
+      SqlParams::Positional(values) => {
+        if let Some(name) = (1..=count)
+          .filter_map(|i| stmt.parameter_name(i))
+          .find(|n| !n.starts_with('?'))
+        {
+          return Err(napi::Error::from_reason(format!(
+            "SQL uses named parameter '{}' but params is an array; pass an object instead",
+            name
+          )));
+        }
+        if values.len() != count {
+          return Err(napi::Error::from_reason(format!(
+            "SQL expects {} parameter(s) but {} were given",
+            count,
+            values.len()
+          )));
+        }
+        for (i, value) in values.iter().enumerate() {
+          stmt
+            .raw_bind_parameter(i + 1, value)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        }
+      }
+      SqlParams::Named(values) => {
+        for i in 1..=count {
+          // Anonymous `?` placeholders have no name at all
+          let name = match stmt.parameter_name(i) {
+            Some(name) if !name.starts_with('?') => name.to_string(),
             _ => {
-                map.insert(key, value);
+              return Err(napi::Error::from_reason(
+                "SQL uses positional parameters but params is an object; pass an array instead",
+              ))
             }
+          };
+          let value = values.get(&name[1..]).ok_or_else(|| {
+            napi::Error::from_reason(format!("Missing value for named parameter '{}'", name))
+          })?;
+          stmt
+            .raw_bind_parameter(i, value)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
         }
+      }
     }
+    Ok(())
+  }
+}
 
-    Ok(map)
+pub fn collect_rows<T>(
+  stmt: &mut Statement,
+  params: SqlParams,
+  mut f: impl FnMut(&Row) -> rusqlite::Result<T>,
+) -> Result<Vec<T>> {
+  params.bind(stmt)?;
+  let mut rows = stmt.raw_query();
+  let mut results = Vec::new();
+  while let Some(row) = rows
+    .next()
+    .map_err(|e| napi::Error::from_reason(e.to_string()))?
+  {
+    results.push(f(row).map_err(|e| napi::Error::from_reason(e.to_string()))?);
+  }
+  Ok(results)
 }
 
+// A JS Date as whole unix seconds, whatever the dateFormat option says
+pub fn date_to_unix_seconds(val: JsUnknown) -> napi::Result<i64> {
+  if !val.is_date()? {
+    return Err(napi::Error::from_reason("Expected a Date"));
+  }
+  let ms = unsafe { val.cast::<napi::JsDate>() }.value_of()?;
+  if ms.is_nan() {
+    return Err(napi::Error::from_reason("Invalid Date"));
+  }
+  Ok((ms / 1000.0).floor() as i64)
+}
 
-pub fn js_unknown_to_rusqlite_value(val: JsUnknown) -> napi::Result<rusqlite::types::Value> {
-    let val_type = val.get_type()?;
+fn date_to_rusqlite_value(
+  val: JsUnknown,
+  format: DateFormat,
+) -> napi::Result<rusqlite::types::Value> {
+  let date = unsafe { val.cast::<napi::JsDate>() };
+  match format {
+    DateFormat::Iso => {
+      let obj = date.coerce_to_object()?;
+      let to_iso = obj.get_named_property::<JsFunction>("toISOString")?;
+      let iso = to_iso
+        .call_without_args(Some(&obj))?
+        .coerce_to_string()?
+        .into_utf8()?;
+      Ok(rusqlite::types::Value::Text(iso.as_str()?.to_owned()))
+    }
+    DateFormat::Unix => Ok(rusqlite::types::Value::Integer(
+      (date.value_of()? / 1000.0).floor() as i64,
+    )),
+    DateFormat::UnixMs => Ok(rusqlite::types::Value::Integer(date.value_of()? as i64)),
+  }
+}
 
-    match val_type {
-        ValueType::Null | ValueType::Undefined => Ok(rusqlite::types::Value::Null),
+pub fn js_unknown_to_typed_value(
+  val: JsUnknown,
+  affinity: &str,
+  config: &Config,
+) -> napi::Result<rusqlite::types::Value> {
+  if matches!(val.get_type()?, ValueType::Null | ValueType::Undefined) {
+    return Ok(rusqlite::types::Value::Null);
+  }
 
-        ValueType::Boolean => {
-            let bool_val = val.coerce_to_bool()?.get_value()?;
-            Ok(rusqlite::types::Value::Integer(if bool_val { 1 } else { 0 }))
-        }
+  match affinity.to_lowercase().as_str() {
+    "text" => {
+      let str_val = val.coerce_to_string()?.into_utf8()?;
+      Ok(rusqlite::types::Value::Text(str_val.as_str()?.to_owned()))
+    }
+    "integer" => {
+      let num = val.coerce_to_number()?;
+      let num_val = num.get_double()?;
+      if !num_val.is_finite() {
+        return real_value(num_val, config);
+      }
+      Ok(rusqlite::types::Value::Integer(num.get_int64()?))
+    }
+    "real" => real_value(val.coerce_to_number()?.get_double()?, config),
+    "numeric" => js_unknown_to_rusqlite_value(val, config),
+    other => Err(napi::Error::from_reason(format!(
+      "Unknown type hint '{}', expected one of text, integer, real, numeric",
+      other
+    ))),
+  }
+}
 
-        ValueType::Number => {
-            let num_val = val.coerce_to_number()?.get_double()?;
-            if num_val.fract() == 0.0 {
-        // Bilangan bulat
-                Ok(rusqlite::types::Value::Integer(num_val as i64))
-            } else {
-        // Bilangan desimal
-                Ok(rusqlite::types::Value::Real(num_val))
-            }
-        }
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rusqlite::types::Value;
 
-        ValueType::String => {
-            let str_val = val.coerce_to_string()?.into_utf8()?;
-            Ok(rusqlite::types::Value::Text(str_val.as_str()?.to_owned()))
-        }
+  #[test]
+  fn escape_like_escapes_wildcards_and_backslash() {
+    assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    assert_eq!(escape_like("plain"), "plain");
+  }
 
-        _ => Ok(rusqlite::types::Value::Null),
-    }
+  #[test]
+  fn enum_values_reads_back_enum_column_sql() {
+    let values = vec!["draft".to_string(), "it's".to_string(), "a, b".to_string()];
+    let create_sql = format!(
+      "CREATE TABLE posts (id INTEGER, {})",
+      enum_column_sql("status", &values)
+    );
+    assert_eq!(enum_values(&create_sql, "status"), Some(values));
+    assert_eq!(enum_values(&create_sql, "missing"), None);
+    assert_eq!(
+      enum_values("CREATE TABLE t (\"s\" TEXT CHECK (\"s\" IN ('a'", "s"),
+      None
+    );
+  }
+
+  #[test]
+  fn inline_params_skips_quoted_question_marks() {
+    let sql = inline_params(
+      "a = ? AND b = '?' AND \"c?\" = ?",
+      &[Value::Integer(1), Value::Text("O'Brien".into())],
+    )
+    .unwrap();
+    assert_eq!(sql, "a = 1 AND b = '?' AND \"c?\" = 'O''Brien'");
+    assert!(inline_params("a = ? AND b = ?", &[Value::Integer(1)]).is_err());
+    assert!(inline_params("a = ?", &[Value::Integer(1), Value::Integer(2)]).is_err());
+  }
+
+  #[test]
+  fn validate_type_spec_allows_plain_constraints_only() {
+    assert!(validate_type_spec("TEXT NOT NULL DEFAULT 'x'").is_ok());
+    assert!(validate_type_spec("DECIMAL(10, 2)").is_ok());
+    assert!(validate_type_spec("INTEGER -- comment").is_err());
+    assert!(validate_type_spec("TEXT DEFAULT 'x").is_err());
+    assert!(validate_type_spec("TEXT); DROP TABLE users; (").is_err());
+    assert!(validate_type_spec("(TEXT)").is_err());
+    assert!(validate_type_spec("").is_err());
+  }
+
+  #[test]
+  fn identifiers() {
+    assert!(is_valid_identifier("_user_2"));
+    assert!(!is_valid_identifier("2users"));
+    assert!(!is_valid_identifier("main.users"));
+    assert!(!is_valid_identifier("name) OR 1=1"));
+    assert!(!is_valid_identifier(""));
+
+    assert!(is_valid_qualified_identifier("users"));
+    assert!(is_valid_qualified_identifier("main.users"));
+    assert!(!is_valid_qualified_identifier("a.b.c"));
+    assert!(!is_valid_qualified_identifier("main."));
+    assert!(!is_valid_qualified_identifier(".users"));
+    assert!(!is_valid_qualified_identifier("users; --"));
+  }
+
+  #[test]
+  fn table_names_quote_each_part() {
+    assert_eq!(quote_table_name("users"), "\"users\"");
+    assert_eq!(quote_table_name("main.users"), "\"main\".\"users\"");
+    assert_eq!(split_table_name("users"), (String::new(), "users"));
+    assert_eq!(
+      split_table_name("aux.users"),
+      ("\"aux\".".to_string(), "users")
+    );
+  }
+
+  #[test]
+  fn base64_encode_pads() {
+    assert_eq!(base64_encode(b""), "");
+    assert_eq!(base64_encode(b"f"), "Zg==");
+    assert_eq!(base64_encode(b"fo"), "Zm8=");
+    assert_eq!(base64_encode(b"foo"), "Zm9v");
+    assert_eq!(base64_encode(&[0xfb, 0xff]), "+/8=");
+  }
+
+  #[test]
+  fn sql_literal_quotes_values() {
+    assert_eq!(sql_literal(&Value::Null), "NULL");
+    assert_eq!(sql_literal(&Value::Integer(-3)), "-3");
+    assert_eq!(sql_literal(&Value::Real(1.0)), "1.0");
+    assert_eq!(sql_literal(&Value::Text("O'Brien".into())), "'O''Brien'");
+    assert_eq!(sql_literal(&Value::Blob(vec![0, 0xab])), "X'00AB'");
+  }
 }
@@ -1,262 +1,1851 @@
-use napi::{Env, JsObject, JsUnknown, Result, ValueType, JsString};
+use napi::bindgen_prelude::{AsyncTask, BigInt, ClassInstance, Either3, FromNapiValue, Null};
+use napi::{Env, JsFunction, JsObject, JsString, JsUnknown, NapiRaw, Result, ValueType};
 use napi_derive::napi;
 
-use crate::extra::{row_to_object};
-use crate::table::{Table};
+use crate::cache::{cached_query, cached_rows_to_objects, table_columns};
+use crate::extra::{
+  bindings_to_js, column_keys, count_to_js, csv_field, date_to_unix_seconds, describe_value,
+  enum_values, escape_like, is_valid_identifier, is_valid_qualified_identifier,
+  js_object_to_hashmap, js_unknown_to_rusqlite_value, query_to_arrays, quote_identifier,
+  quote_table_name, row_to_keyed_object, row_to_object, split_table_name, sql_literal,
+  supports_update_delete_limit, BindValue, DefaultOperator, OwnedParams, SqlParams,
+};
+use crate::table::{RunResult, Table};
+use crate::task::{parse_json_columns, AsyncQueryOptions, QueryTask};
 
 use napi::{CallContext, JsUndefined};
 use napi_derive::js_function;
 
 #[js_function(1)]
 fn update_callback(ctx: CallContext) -> Result<JsUndefined> {
-    let this = ctx.this_unchecked::<JsObject>();
-    let filter = ctx.env.unwrap::<FilteredTable>(&this)?;
-    let data = ctx.get::<JsObject>(0)?;
-    filter.update(data)?;
-    ctx.env.get_undefined()
+  let this = ctx.this_unchecked::<JsObject>();
+  let filter = ctx.env.unwrap::<FilteredTable>(&this)?;
+  let data = ctx.get::<JsObject>(0)?;
+  filter.update(*ctx.env, data, None)?;
+  ctx.env.get_undefined()
 }
 
 #[js_function(1)]
 fn destroy_callback(ctx: CallContext) -> Result<JsUndefined> {
-    let this = ctx.this_unchecked::<JsObject>();
-    let filter = ctx.env.unwrap::<FilteredTable>(&this)?;
-    filter.destroy()?;
-    ctx.env.get_undefined()
+  let this = ctx.this_unchecked::<JsObject>();
+  let filter = ctx.env.unwrap::<FilteredTable>(&this)?;
+  filter.destroy(*ctx.env, None)?;
+  ctx.env.get_undefined()
+}
+
+fn typed_column(ctx: &CallContext, expected: ValueType) -> Result<JsUnknown> {
+  let this = ctx.this_unchecked::<JsObject>();
+  let column = ctx.get::<JsString>(0)?.into_utf8()?.as_str()?.to_owned();
+  if !this.has_own_property(&column)? {
+    return Err(napi::Error::from_reason(format!(
+      "Column {} is not in the row",
+      column
+    )));
+  }
+  let value = this.get_named_property::<JsUnknown>(&column)?;
+  let actual = value.get_type()?;
+  if actual != expected {
+    let found = match actual {
+      ValueType::Undefined | ValueType::Null => "NULL".to_string(),
+      other => format!("a {}", other.to_string().to_lowercase()),
+    };
+    return Err(napi::Error::from_reason(format!(
+      "Column {} is {}, expected a {}",
+      column,
+      found,
+      expected.to_string().to_lowercase()
+    )));
+  }
+  Ok(value)
+}
+
+#[js_function(1)]
+fn get_string_callback(ctx: CallContext) -> Result<JsUnknown> {
+  typed_column(&ctx, ValueType::String)
+}
+
+#[js_function(1)]
+fn get_number_callback(ctx: CallContext) -> Result<JsUnknown> {
+  typed_column(&ctx, ValueType::Number)
+}
+
+// The row's columns as a plain object, without the attached methods
+#[js_function(1)]
+fn to_object_callback(ctx: CallContext) -> Result<JsObject> {
+  let this = ctx.this_unchecked::<JsObject>();
+  let mut plain = ctx.env.create_object()?;
+  let keys = this.get_property_names()?;
+  for i in 0..keys.get_array_length()? {
+    let key = keys
+      .get_element::<JsString>(i)?
+      .into_utf8()?
+      .as_str()?
+      .to_owned();
+    let value = this.get_named_property::<JsUnknown>(&key)?;
+    if value.get_type()? != ValueType::Function {
+      plain.set_named_property(&key, value)?;
+    }
+  }
+  Ok(plain)
 }
 
 fn attach_ops(env: Env, obj: JsObject, filter: FilteredTable) -> Result<JsObject> {
-    let mut wrapped = env.create_object()?;
-    let keys = obj.get_property_names()?;
-    let len = keys.get_array_length()?;
+  let mut wrapped = env.create_object()?;
+  let keys = obj.get_property_names()?;
+  let len = keys.get_array_length()?;
+
+  for i in 0..len {
+    let key = keys.get_element::<JsString>(i)?;
+    let key_utf8 = key.into_utf8()?;
+    let key_str = key_utf8.as_str()?;
+    let value = obj.get_named_property::<JsUnknown>(key_str)?;
+    wrapped.set_named_property(key_str, value)?;
+  }
+
+  env.wrap(&mut wrapped, filter)?;
+
+  let update = env.create_function("update", update_callback)?;
+  let destroy = env.create_function("destroy", destroy_callback)?;
+
+  wrapped.set_named_property("update", &update)?;
+  wrapped.set_named_property("destroy", &destroy)?;
+  wrapped.set_named_property("delete", &destroy)?;
+  wrapped.set_named_property(
+    "getString",
+    env.create_function("getString", get_string_callback)?,
+  )?;
+  wrapped.set_named_property(
+    "getNumber",
+    env.create_function("getNumber", get_number_callback)?,
+  )?;
+  wrapped.set_named_property(
+    "toObject",
+    env.create_function("toObject", to_object_callback)?,
+  )?;
+
+  Ok(wrapped)
+}
+
+#[napi(object)]
+pub struct CsvOptions {
+  pub chunk_rows: Option<u32>,
+}
+
+#[napi(object)]
+pub struct PaginateOptions {
+  // Add nextPage/prevPage to the result
+  pub links: Option<bool>,
+}
+
+#[napi(object)]
+pub struct Page {
+  pub data: Vec<JsObject>,
+  pub total: i64,
+  pub page: i64,
+  pub per_page: i64,
+  pub last_page: i64,
+  // Only set with the `links` option; null past either end
+  pub next_page: Option<napi::Either<i64, Null>>,
+  pub prev_page: Option<napi::Either<i64, Null>>,
+}
+
+#[napi(object)]
+pub struct WriteOptions {
+  pub limit: Option<i64>,
+  pub returning: Option<bool>,
+  pub result: Option<bool>,
+  pub debug: Option<bool>,
+}
+
+// The number of changed rows by default, the rows themselves with `returning`, or a RunResult
+// with `result`/`debug`
+pub(crate) type WriteResult = Either3<i64, Vec<JsObject>, RunResult>;
 
-    for i in 0..len {
-        let key = keys.get_element::<JsString>(i)?;
-        let key_utf8 = key.into_utf8()?;
-        let key_str = key_utf8.as_str()?;
-        let value = obj.get_named_property::<JsUnknown>(key_str)?;
-        wrapped.set_named_property(key_str, value)?;
+// Returns (limit, returning, result, debug); `debug` implies a result object
+fn parse_write_options(options: Option<WriteOptions>) -> Result<(Option<i64>, bool, bool, bool)> {
+  let Some(options) = options else {
+    return Ok((None, false, false, false));
+  };
+  let returning = options.returning.unwrap_or(false);
+  let debug = options.debug.unwrap_or(false);
+  let result = options.result.unwrap_or(false) || debug;
+  if returning && result {
+    return Err(napi::Error::from_reason(
+      "The returning option can't be combined with result or debug",
+    ));
+  }
+  Ok((options.limit, returning, result, debug))
+}
+
+// A SQL fragment with the values for its `?` placeholders. Statements are assembled
+// from segments in clause order, so raw and structured bindings always line up with
+// the placeholders regardless of the order the builder methods were called in.
+#[derive(Clone, Default)]
+pub(crate) struct Segment {
+  pub(crate) sql: String,
+  pub(crate) params: Vec<rusqlite::types::Value>,
+}
+
+impl Segment {
+  pub(crate) fn new(sql: impl Into<String>, params: Vec<rusqlite::types::Value>) -> Self {
+    Segment {
+      sql: sql.into(),
+      params,
     }
+  }
 
-    env.wrap(&mut wrapped, filter)?;
+  fn push_sql(&mut self, sql: &str) {
+    self.sql.push_str(sql);
+  }
 
-    let update = env.create_function("update", update_callback)?;
-    let destroy = env.create_function("destroy", destroy_callback)?;
+  fn push(&mut self, other: &Segment) {
+    self.sql.push_str(&other.sql);
+    self.params.extend(other.params.iter().cloned());
+  }
 
-    wrapped.set_named_property("update", &update)?;
-    wrapped.set_named_property("destroy", &destroy)?;
-    wrapped.set_named_property("delete", &destroy)?;
+  fn push_joined(&mut self, keyword: &str, segments: &[Segment], separator: &str) {
+    for (i, segment) in segments.iter().enumerate() {
+      self.push_sql(if i == 0 { keyword } else { separator });
+      self.push(segment);
+    }
+  }
+}
 
-    Ok(wrapped)
+#[derive(Clone)]
+pub(crate) enum Condition {
+  Basic {
+    column: String,
+    operator: String,
+    value: rusqlite::types::Value,
+  },
+  Group(Vec<Condition>),
+  Not(Box<Condition>),
+  Raw {
+    sql: String,
+    params: Vec<rusqlite::types::Value>,
+  },
+}
+
+impl Condition {
+  fn to_sql(&self, params: &mut Vec<rusqlite::types::Value>) -> String {
+    let (col, op, val) = match self {
+      Condition::Basic {
+        column,
+        operator,
+        value,
+      } => (column, operator, value),
+      Condition::Group(conditions) => {
+        let clauses: Vec<String> = conditions.iter().map(|c| c.to_sql(params)).collect();
+        return format!("({})", clauses.join(" AND "));
+      }
+      Condition::Raw {
+        sql,
+        params: bindings,
+      } => {
+        params.extend(bindings.iter().cloned());
+        return sql.clone();
+      }
+      Condition::Not(condition) => {
+        return match condition.as_ref() {
+          Condition::Group(_) => format!("NOT {}", condition.to_sql(params)),
+          _ => format!("NOT ({})", condition.to_sql(params)),
+        };
+      }
+    };
+    match op.to_uppercase().as_str() {
+      "IS NULL" | "IS NOT NULL" => format!("{col} {op}"),
+      "IN" => {
+        let items: Vec<rusqlite::types::Value> = match val {
+          rusqlite::types::Value::Text(s) => s
+            .split(',')
+            .map(|item| rusqlite::types::Value::Text(item.trim().to_string()))
+            .collect(),
+          other => vec![other.clone()],
+        };
+        let sql = format!("{} IN ({})", col, vec!["?"; items.len()].join(", "));
+        params.extend(items);
+        sql
+      }
+      _ => {
+        params.push(val.clone());
+        format!("{col} {op} ?")
+      }
+    }
+  }
 }
 
 #[napi]
 #[derive(Clone)]
 pub struct FilteredTable {
-    pub(crate) table: Table,
-    pub(crate) column: String,
-    pub(crate) operator: String,
-    pub(crate) value: napi::Either<String, i64>,
-    pub(crate) extra_conditions: Vec<(String, String, napi::Either<String, i64>)>,
-    pub(crate) order_by: Option<(String, String)>,
+  pub(crate) table: Table,
+  pub(crate) conditions: Vec<Condition>,
+  pub(crate) order_by: Option<(String, String)>,
+  pub(crate) columns: Option<Vec<String>>,
+  // Relations loaded as JSON arrays: (relation name, correlated subquery)
+  pub(crate) json_relations: Vec<(String, String)>,
+  pub(crate) raw_columns: Vec<Segment>,
+  pub(crate) group_by: Vec<String>,
+  pub(crate) having: Vec<Segment>,
+  // Raw ORDER BY terms, applied after the structured order_by column
+  pub(crate) raw_order: Vec<Segment>,
+  pub(crate) limit: Option<i64>,
+  pub(crate) offset: Option<i64>,
+}
+
+impl FilteredTable {
+  pub(crate) fn new(table: Table) -> Self {
+    FilteredTable {
+      table,
+      conditions: vec![],
+      order_by: None,
+      columns: None,
+      json_relations: vec![],
+      raw_columns: vec![],
+      group_by: vec![],
+      having: vec![],
+      raw_order: vec![],
+      limit: None,
+      offset: None,
+    }
+  }
+
+  fn raw_segment(&self, sql: String, bindings: Option<Vec<JsUnknown>>) -> Result<Segment> {
+    let params = bindings
+      .unwrap_or_default()
+      .into_iter()
+      .map(|v| js_unknown_to_rusqlite_value(v, &self.table.config))
+      .collect::<Result<Vec<_>>>()?;
+    Ok(Segment::new(sql, params))
+  }
+
+  pub(crate) fn with_condition(
+    mut self,
+    column: &str,
+    operator: &str,
+    value: rusqlite::types::Value,
+  ) -> Self {
+    self.conditions.push(Condition::Basic {
+      column: column.to_string(),
+      operator: operator.to_string(),
+      value,
+    });
+    self
+  }
+}
+
+// Column names passed to the builder methods go into the SQL unquoted
+fn check_column(column: &str) -> Result<()> {
+  if is_valid_qualified_identifier(column) {
+    Ok(())
+  } else {
+    Err(napi::Error::from_reason(format!(
+      "Invalid column name '{}': use letters, digits and _ (or table.column)",
+      column
+    )))
+  }
+}
+
+const PREALLOCATED_ROWS_MAX: i64 = 100_000;
+
+// Comparison operators are spliced into the SQL too, so only these are accepted (normalized to
+// upper case with single spaces)
+const OPERATORS: &[&str] = &[
+  "=",
+  "!=",
+  "<>",
+  "<",
+  "<=",
+  ">",
+  ">=",
+  "LIKE",
+  "NOT LIKE",
+  "GLOB",
+  "IS",
+  "IS NOT",
+  "IN",
+  "IS NULL",
+  "IS NOT NULL",
+];
+
+fn check_operator(operator: &str) -> Result<String> {
+  let normalized = operator
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+    .to_ascii_uppercase();
+  if OPERATORS.contains(&normalized.as_str()) {
+    Ok(normalized)
+  } else {
+    Err(napi::Error::from_reason(format!(
+      "Unsupported operator '{}', expected one of: {}",
+      operator,
+      OPERATORS.join(", ")
+    )))
+  }
+}
+
+fn sort_direction(direction: Option<String>) -> Result<String> {
+  match direction {
+    None => Ok("ASC".to_string()),
+    Some(d) if d.eq_ignore_ascii_case("ASC") || d.eq_ignore_ascii_case("DESC") => {
+      Ok(d.to_ascii_uppercase())
+    }
+    Some(d) => Err(napi::Error::from_reason(format!(
+      "Sort direction must be 'asc' or 'desc', got '{}'",
+      d
+    ))),
+  }
+}
+
+pub(crate) type WhereValue = napi::bindgen_prelude::Either3<String, i64, bool>;
+
+pub(crate) fn where_value(value: WhereValue) -> rusqlite::types::Value {
+  match value {
+    napi::bindgen_prelude::Either3::A(s) => rusqlite::types::Value::Text(s),
+    napi::bindgen_prelude::Either3::B(i) => rusqlite::types::Value::Integer(i),
+    // Booleans are stored as 0/1, so filter on the same representation
+    napi::bindgen_prelude::Either3::C(b) => rusqlite::types::Value::Integer(b as i64),
+  }
+}
+
+fn parse_where_args(
+  op_or_value: napi::Either<String, WhereValue>,
+  value_opt: Option<WhereValue>,
+  default_operator: DefaultOperator,
+) -> Result<(String, rusqlite::types::Value)> {
+  let default_operator = default_operator.sql().to_string();
+  match (op_or_value, value_opt) {
+    (napi::Either::A(op), Some(v)) => Ok((check_operator(&op)?, where_value(v))),
+    (_, Some(v)) => Ok((default_operator, where_value(v))),
+    // A lone string is always read as the first variant, so it is the compared value
+    (napi::Either::A(v), None) => Ok((default_operator, rusqlite::types::Value::Text(v))),
+    (napi::Either::B(v), None) => Ok((default_operator, where_value(v))),
+  }
 }
 
 #[napi]
 impl FilteredTable {
-    #[napi]
-    pub fn first(&self, env: Env) -> Result<Option<JsObject>> {
-        let mut filtered = self.clone();
-        filtered.order_by = Some((
-            self.order_by
-                .as_ref()
-                .map(|(col, _)| col.clone())
-                .unwrap_or_else(|| "rowid".to_string()), // default order by
-            "ASC".to_string(),
-        ));
-        let mut iter = filtered.all(env)?.into_iter();
-        Ok(iter.next().map(|obj| attach_ops(env, obj, self.clone())).transpose()?)
-    }
-
-    #[napi]
-    pub fn last(&self, env: Env) -> Result<Option<JsObject>> {
-        let mut filtered = self.clone();
-        filtered.order_by = Some((
-            self.order_by
-                .as_ref()
-                .map(|(col, _)| col.clone())
-                .unwrap_or_else(|| "rowid".to_string()),
-            "DESC".to_string(),
-        ));
-        let mut iter = filtered.all(env)?.into_iter();
-        Ok(iter.next().map(|obj| attach_ops(env, obj, self.clone())).transpose()?)
-    }
-
-    #[napi]
-    pub fn order_by(&mut self, column: String, direction: Option<String>) -> Result<Self> {
-        self.order_by = Some((column, direction.unwrap_or_else(|| "ASC".into())));
-        Ok(self.clone())
-    }
-
-    #[napi]
-    pub fn where_(
-        &self,
-        column: String,
-        op_or_value: napi::Either<String, napi::Either<String, i64>>,
-        value_opt: Option<napi::Either<String, i64>>,
-    ) -> Result<FilteredTable> {
-        let (operator, value) = if let Some(v) = value_opt {
-            let op = match op_or_value {
-                napi::Either::A(op) => op,
-                _ => "=".into(),
-            };
-            (op, v)
+  #[napi]
+  pub fn first(&self, env: Env) -> Result<Option<JsObject>> {
+    let mut filtered = self.clone();
+    filtered.limit = Some(1);
+    // CTEs have no rowid to fall back on, so keep the order they produce
+    if self.order_by.is_none() && (self.table.is_cte() || !self.raw_order.is_empty()) {
+      let row = filtered.all(env)?.into_iter().next();
+      return row
+        .map(|obj| attach_ops(env, obj, self.clone()))
+        .transpose();
+    }
+    filtered.order_by = Some(
+      self
+        .order_by
+        .clone()
+        .unwrap_or_else(|| ("rowid".to_string(), "ASC".to_string())), // default order by
+    );
+    let mut iter = filtered.all(env)?.into_iter();
+    iter
+      .next()
+      .map(|obj| attach_ops(env, obj, self.clone()))
+      .transpose()
+  }
+
+  #[napi]
+  pub fn last(&self, env: Env) -> Result<Option<JsObject>> {
+    // Reversing the order would also move a limit/offset window, so those read the window
+    let paged = self.limit.is_some() || self.offset.is_some();
+    if paged || (self.order_by.is_none() && (self.table.is_cte() || !self.raw_order.is_empty())) {
+      let row = self.all(env)?.into_iter().last();
+      return row
+        .map(|obj| attach_ops(env, obj, self.clone()))
+        .transpose();
+    }
+    // The last row in the requested order is the first one in the reversed order
+    let mut filtered = self.clone();
+    filtered.limit = Some(1);
+    filtered.order_by = Some(match &self.order_by {
+      Some((col, dir)) if dir.eq_ignore_ascii_case("DESC") => (col.clone(), "ASC".to_string()),
+      Some((col, _)) => (col.clone(), "DESC".to_string()),
+      None => ("rowid".to_string(), "DESC".to_string()),
+    });
+    let mut iter = filtered.all(env)?.into_iter();
+    iter
+      .next()
+      .map(|obj| attach_ops(env, obj, self.clone()))
+      .transpose()
+  }
+
+  #[napi]
+  pub fn first_or_fail(&self, env: Env) -> Result<JsObject> {
+    self.first(env)?.ok_or_else(|| {
+      let (sql, params) = self.select_sql();
+      let bindings = params
+        .iter()
+        .map(describe_value)
+        .collect::<Vec<_>>()
+        .join(", ");
+      napi::Error::from_reason(format!(
+        "No row found for `{}` with bindings [{}]",
+        sql, bindings
+      ))
+    })
+  }
+
+  #[napi]
+  pub fn to_sql(&self) -> String {
+    self.select_sql().0
+  }
+
+  // An independent copy to branch from; orderBy still changes the builder it's called on
+  #[napi]
+  pub fn clone_builder(&self) -> FilteredTable {
+    self.clone()
+  }
+
+  // ORDER BY can't take a bound column, so a user-supplied sort column (e.g. a `sortBy` request
+  // parameter) is only accepted when it is exactly one of `allowed_columns`
+  #[napi]
+  pub fn order_by_safe(
+    &mut self,
+    column: String,
+    direction: Option<String>,
+    allowed_columns: Vec<String>,
+  ) -> Result<Self> {
+    if !allowed_columns.contains(&column) {
+      return Err(napi::Error::from_reason(format!(
+        "Sorting by '{}' is not allowed",
+        column
+      )));
+    }
+    self.order_by(column, direction)
+  }
+
+  #[napi]
+  pub fn limit(&self, n: i64) -> Result<FilteredTable> {
+    if n < 1 {
+      return Err(napi::Error::from_reason("Limit must be a positive integer"));
+    }
+    let mut filtered = self.clone();
+    filtered.limit = Some(n);
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn offset(&self, n: i64) -> Result<FilteredTable> {
+    if n < 0 {
+      return Err(napi::Error::from_reason("Offset must not be negative"));
+    }
+    let mut filtered = self.clone();
+    filtered.offset = Some(n);
+    Ok(filtered)
+  }
+
+  // One page of rows plus the totals a paginated response needs; pages count from 1
+  #[napi]
+  pub fn paginate(
+    &self,
+    env: Env,
+    page: i64,
+    per_page: i64,
+    options: Option<PaginateOptions>,
+  ) -> Result<Page> {
+    if page < 1 {
+      return Err(napi::Error::from_reason("Page must be a positive integer"));
+    }
+    if per_page < 1 {
+      return Err(napi::Error::from_reason(
+        "perPage must be a positive integer",
+      ));
+    }
+    let mut filtered = self.clone();
+    filtered.limit = None;
+    filtered.offset = None;
+    let total: i64 = filtered.aggregate("COUNT(*)")?;
+    let last_page = ((total + per_page - 1) / per_page).max(1);
+    let data = filtered
+      .limit(per_page)?
+      .offset((page - 1).saturating_mul(per_page))?
+      .all(env)?;
+
+    let (next_page, prev_page) = if options.and_then(|o| o.links).unwrap_or(false) {
+      let link = |target: i64, valid: bool| {
+        Some(if valid {
+          napi::Either::A(target)
         } else {
-            match op_or_value {
-                napi::Either::B(v) => ("=".into(), v),
-                _ => return Err(napi::Error::from_reason("Invalid arguments for where")),
+          napi::Either::B(Null)
+        })
+      };
+      (
+        link(page + 1, page < last_page),
+        link((page - 1).min(last_page), page > 1),
+      )
+    } else {
+      (None, None)
+    };
+    Ok(Page {
+      data,
+      total,
+      page,
+      per_page,
+      last_page,
+      next_page,
+      prev_page,
+    })
+  }
+
+  #[napi]
+  pub fn order_by(&mut self, column: String, direction: Option<String>) -> Result<Self> {
+    check_column(&column)?;
+    self.order_by = Some((column, sort_direction(direction)?));
+    Ok(self.clone())
+  }
+
+  #[napi]
+  pub fn latest(&self, column: Option<String>) -> Result<FilteredTable> {
+    let column = column.unwrap_or_else(|| "created_at".into());
+    check_column(&column)?;
+    let mut filtered = self.clone();
+    filtered.order_by = Some((column, "DESC".into()));
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn oldest(&self, column: Option<String>) -> Result<FilteredTable> {
+    let column = column.unwrap_or_else(|| "created_at".into());
+    check_column(&column)?;
+    let mut filtered = self.clone();
+    filtered.order_by = Some((column, "ASC".into()));
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn where_(
+    &self,
+    column_or_equalities: napi::Either<String, JsObject>,
+    op_or_value: Option<napi::Either<String, WhereValue>>,
+    value_opt: Option<WhereValue>,
+  ) -> Result<FilteredTable> {
+    let column = match column_or_equalities {
+      napi::Either::A(column) => column,
+      // `where({ status: 'active', role: 'admin' })`: one `=` (or the default operator) per
+      // key, ANDed
+      napi::Either::B(equalities) => {
+        let mut filtered = self.clone();
+        let keys = equalities.get_property_names()?;
+        for i in 0..keys.get_array_length()? {
+          let key = keys
+            .get_element::<JsString>(i)?
+            .into_utf8()?
+            .as_str()?
+            .to_owned();
+          check_column(&key)?;
+          let value = equalities.get_named_property::<JsUnknown>(&key)?;
+          match value.get_type()? {
+            // Skipped, as update() skips them
+            ValueType::Undefined => continue,
+            ValueType::Null
+            | ValueType::Boolean
+            | ValueType::Number
+            | ValueType::BigInt
+            | ValueType::String => {}
+            ValueType::Object if value.is_date()? || value.is_buffer()? => {}
+            _ => {
+              return Err(napi::Error::from_reason(format!(
+                                "where() can't compare {} with an array, object or function; use whereIn or whereJsonMatch instead",
+                                key
+                            )));
             }
-        };
+          }
+          filtered = match js_unknown_to_rusqlite_value(value, &self.table.config)? {
+            rusqlite::types::Value::Null => {
+              filtered.with_condition(&key, "IS NULL", rusqlite::types::Value::Null)
+            }
+            value => filtered.with_condition(&key, self.table.config.default_operator.sql(), value),
+          };
+        }
+        return Ok(filtered);
+      }
+    };
+    check_column(&column)?;
+    let op_or_value =
+      op_or_value.ok_or_else(|| napi::Error::from_reason("Invalid arguments for where"))?;
+    let (operator, value) =
+      parse_where_args(op_or_value, value_opt, self.table.config.default_operator)?;
+    Ok(self.clone().with_condition(&column, &operator, value))
+  }
+
+  #[napi]
+  pub fn select(&self, columns: Vec<String>) -> Result<FilteredTable> {
+    if columns.is_empty() {
+      return Err(napi::Error::from_reason("select needs at least one column"));
+    }
+    for column in columns.iter().filter(|column| *column != "*") {
+      check_column(column)?;
+    }
+    let mut filtered = self.clone();
+    filtered.columns = Some(columns);
+    Ok(filtered)
+  }
 
-        let mut extra = self.extra_conditions.clone();
-        extra.push((self.column.clone(), self.operator.clone(), self.value.clone()));
+  #[napi]
+  pub fn where_raw(&self, sql: String, bindings: Option<Vec<JsUnknown>>) -> Result<FilteredTable> {
+    let segment = self.raw_segment(format!("({})", sql), bindings)?;
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw {
+      sql: segment.sql,
+      params: segment.params,
+    });
+    Ok(filtered)
+  }
 
-        Ok(Self {
-            table: self.table.clone(),
-            column,
-            operator,
-            value,
-            extra_conditions: extra,
-            order_by: None,
-        })
+  #[napi]
+  pub fn select_raw(&self, sql: String, bindings: Option<Vec<JsUnknown>>) -> Result<FilteredTable> {
+    let segment = self.raw_segment(sql, bindings)?;
+    let mut filtered = self.clone();
+    filtered.raw_columns.push(segment);
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn group_by(&self, columns: Vec<String>) -> Result<FilteredTable> {
+    if columns.is_empty() {
+      return Err(napi::Error::from_reason(
+        "groupBy needs at least one column",
+      ));
     }
+    for column in &columns {
+      check_column(column)?;
+    }
+    let mut filtered = self.clone();
+    filtered.group_by = columns;
+    Ok(filtered)
+  }
 
-    #[napi]
-    pub fn get(&self, env: Env) -> Result<Vec<JsObject>> {
-        self.all(env)
-    }
-
-    fn build_conditions(&self, sql: &mut String, params: &mut Vec<rusqlite::types::Value>) {
-        let mut append_condition = |col: &str, op: &str, val: &napi::Either<String, i64>| {
-            match op.to_uppercase().as_str() {
-                "IS NULL" | "IS NOT NULL" => {
-                    sql.push_str(&format!("{col} {op} AND "));
-                }
-                "IN" => {
-                    let val_str = match val {
-                        napi::Either::A(s) => s,
-                        napi::Either::B(i) => &i.to_string(),
-                    };
-                    let items: Vec<&str> = val_str.split(',').map(str::trim).collect();
-                    sql.push_str(&format!(
-                        "{} IN ({}) AND ",
-                        col,
-                        std::iter::repeat("?").take(items.len()).collect::<Vec<_>>().join(", ")
-                    ));
-                    params.extend(items.into_iter().map(|item| rusqlite::types::Value::Text(item.to_string())));
-                }
-                _ => {
-                    sql.push_str(&format!("{col} {op} ? AND "));
-                    params.push(match val {
-                        napi::Either::A(s) => rusqlite::types::Value::Text(s.clone()),
-                        napi::Either::B(i) => rusqlite::types::Value::Integer(*i),
-                    });
-                }
-            }
-        };
+  #[napi]
+  pub fn having_raw(&self, sql: String, bindings: Option<Vec<JsUnknown>>) -> Result<FilteredTable> {
+    let segment = self.raw_segment(format!("({})", sql), bindings)?;
+    let mut filtered = self.clone();
+    filtered.having.push(segment);
+    Ok(filtered)
+  }
 
-        append_condition(&self.column, &self.operator, &self.value);
-        for (col, op, val) in &self.extra_conditions {
-            append_condition(col, op, val);
-        }
+  #[napi]
+  pub fn order_by_raw(
+    &self,
+    sql: String,
+    bindings: Option<Vec<JsUnknown>>,
+  ) -> Result<FilteredTable> {
+    let segment = self.raw_segment(sql, bindings)?;
+    let mut filtered = self.clone();
+    filtered.raw_order.push(segment);
+    Ok(filtered)
+  }
 
-        if sql.ends_with(" AND ") {
-            sql.truncate(sql.len() - 5);
-        }
+  // Values bound to the placeholders of to_sql(), in placeholder order
+  #[napi]
+  pub fn bindings(&self, env: Env) -> Result<Vec<JsUnknown>> {
+    bindings_to_js(env, &self.select_sql().1)
+  }
+
+  // `column IN (...)` over bound values. SQLite's `x IN (1, NULL)` is never true for a NULL x
+  // (and NULL rather than false when x isn't listed), so nulls become `OR column IS NULL`.
+  #[napi]
+  pub fn where_in(&self, column: String, values: Vec<JsUnknown>) -> Result<FilteredTable> {
+    check_column(&column)?;
+    let mut params = Vec::with_capacity(values.len());
+    let mut has_null = false;
+    for value in values {
+      match js_unknown_to_rusqlite_value(value, &self.table.config)? {
+        rusqlite::types::Value::Null => has_null = true,
+        value => params.push(value),
+      }
+    }
+    let sql = match (params.is_empty(), has_null) {
+      (true, false) => "0".to_string(),
+      (true, true) => format!("{column} IS NULL"),
+      (false, false) => format!("{column} IN ({})", vec!["?"; params.len()].join(", ")),
+      (false, true) => format!(
+        "({column} IN ({}) OR {column} IS NULL)",
+        vec!["?"; params.len()].join(", ")
+      ),
+    };
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw { sql, params });
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn where_in_query(
+    &self,
+    column: String,
+    query: ClassInstance<FilteredTable>,
+  ) -> Result<FilteredTable> {
+    check_column(&column)?;
+    // The subquery yields its first selected column
+    let selected = query
+      .columns
+      .as_ref()
+      .and_then(|columns| columns.first())
+      .ok_or_else(|| {
+        napi::Error::from_reason("whereInQuery needs a query with select() called on it")
+      })?;
+    let (subquery, params) =
+      query.with_window(query.select_columns_sql(Segment::new(selected.as_str(), vec![])));
+
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw {
+      sql: format!("{column} IN ({subquery})"),
+      params,
+    });
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn where_fn(
+    &self,
+    func_name: String,
+    args: Vec<String>,
+    operator: String,
+    value: Option<WhereValue>,
+  ) -> Result<FilteredTable> {
+    if !is_valid_identifier(&func_name) {
+      return Err(napi::Error::from_reason(format!(
+        "Invalid SQL function name '{}'",
+        func_name
+      )));
+    }
+    let expression = format!(
+      "{}({})",
+      func_name,
+      args
+        .iter()
+        .map(|arg| quote_identifier(arg))
+        .collect::<Vec<_>>()
+        .join(", ")
+    );
+    let operator = check_operator(&operator)?;
+    let value = value.map_or(rusqlite::types::Value::Null, where_value);
+    Ok(self.clone().with_condition(&expression, &operator, value))
+  }
+
+  #[napi]
+  pub fn within(&self, min_x: f64, max_x: f64, min_y: f64, max_y: f64) -> Result<FilteredTable> {
+    let (schema_prefix, name) = split_table_name(&self.table.name);
+    let columns = {
+      let conn = self.table.lock()?;
+      let create_sql: Option<String> = conn
+        .query_row(
+          &format!(
+            "SELECT sql FROM {}sqlite_master WHERE type = 'table' AND name = ?",
+            schema_prefix
+          ),
+          [name],
+          |row| row.get(0),
+        )
+        .map_err(|e| match e {
+          rusqlite::Error::QueryReturnedNoRows => {
+            napi::Error::from_reason(format!("Unknown table {}", self.table.name))
+          }
+          e => napi::Error::from_reason(e.to_string()),
+        })?;
+      let is_rtree = create_sql.is_some_and(|sql| sql.to_lowercase().contains("using rtree"));
+      if !is_rtree {
+        return Err(napi::Error::from_reason(format!(
+          "{} is not an R*Tree virtual table",
+          self.table.name
+        )));
+      }
+
+      self.table.column_names(&conn)?
+    };
+
+    // R*Tree columns are the id followed by min/max pairs per dimension
+    let [_, col_min_x, col_max_x, col_min_y, col_max_y, ..] = columns.as_slice() else {
+      return Err(napi::Error::from_reason(format!(
+        "{} is not a two-dimensional R*Tree",
+        self.table.name
+      )));
+    };
+
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw {
+      sql: format!(
+        "{} >= ? AND {} <= ? AND {} >= ? AND {} <= ?",
+        quote_identifier(col_max_x),
+        quote_identifier(col_min_x),
+        quote_identifier(col_max_y),
+        quote_identifier(col_min_y),
+      ),
+      params: vec![
+        rusqlite::types::Value::Real(min_x),
+        rusqlite::types::Value::Real(max_x),
+        rusqlite::types::Value::Real(min_y),
+        rusqlite::types::Value::Real(max_y),
+      ],
+    });
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn where_ilike(&self, column: String, pattern: String) -> Result<FilteredTable> {
+    check_column(&column)?;
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw {
+      sql: format!("LOWER({column}) LIKE LOWER(?) ESCAPE '\\'"),
+      params: vec![rusqlite::types::Value::Text(pattern)],
+    });
+    Ok(filtered)
+  }
+
+  // Equality on a Database.enumColumn column, rejecting values outside its CHECK list up front
+  // (which would otherwise just match nothing)
+  #[napi]
+  pub fn where_enum(&self, column: String, value: String) -> Result<FilteredTable> {
+    let allowed = enum_values(&self.table.schema_sql()?, &column).ok_or_else(|| {
+      napi::Error::from_reason(format!(
+        "Column '{}' of {} is not an enum column",
+        column, self.table.name
+      ))
+    })?;
+    if !allowed.contains(&value) {
+      return Err(napi::Error::from_reason(format!(
+        "'{}' is not a valid value for {}, expected one of: {}",
+        value,
+        column,
+        allowed.join(", ")
+      )));
+    }
+    Ok(self.clone().with_condition(
+      &quote_identifier(&column),
+      "=",
+      rusqlite::types::Value::Text(value),
+    ))
+  }
+
+  // `column LIKE 'prefix%'` with the prefix's own `%`, `_` and `\` escaped
+  #[napi]
+  pub fn where_starts_with(&self, column: String, prefix: String) -> Result<FilteredTable> {
+    self.with_like(&column, format!("{}%", escape_like(&prefix)))
+  }
+
+  #[napi]
+  pub fn where_ends_with(&self, column: String, suffix: String) -> Result<FilteredTable> {
+    self.with_like(&column, format!("%{}", escape_like(&suffix)))
+  }
+
+  fn with_like(&self, column: &str, pattern: String) -> Result<FilteredTable> {
+    check_column(column)?;
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw {
+      sql: format!("{column} LIKE ? ESCAPE '\\'"),
+      params: vec![rusqlite::types::Value::Text(pattern)],
+    });
+    Ok(filtered)
+  }
+
+  // Half-open interval: `start <= column < end`. Bounds can be numbers, strings or Dates,
+  // which bind the same way as inserted values
+  #[napi]
+  pub fn where_in_range(
+    &self,
+    column: String,
+    start: JsUnknown,
+    end: JsUnknown,
+  ) -> Result<FilteredTable> {
+    check_column(&column)?;
+    let start = js_unknown_to_rusqlite_value(start, &self.table.config)?;
+    let end = js_unknown_to_rusqlite_value(end, &self.table.config)?;
+    if matches!(start, rusqlite::types::Value::Null) || matches!(end, rusqlite::types::Value::Null)
+    {
+      return Err(napi::Error::from_reason(
+        "whereInRange needs both a start and an end bound",
+      ));
     }
+    Ok(
+      self
+        .clone()
+        .with_condition(&column, ">=", start)
+        .with_condition(&column, "<", end),
+    )
+  }
 
-    #[napi]
-    pub fn all(&self, env: Env) -> Result<Vec<JsObject>> {
-        let mut sql = format!("SELECT * FROM {} WHERE ", self.table.name);
-        let mut params = Vec::new();
-        self.build_conditions(&mut sql, &mut params);
+  // For dates stored as integer unix seconds: `column BETWEEN start AND end`, both inclusive
+  #[napi]
+  pub fn where_timestamp_between(
+    &self,
+    column: String,
+    start: JsUnknown,
+    end: JsUnknown,
+  ) -> Result<FilteredTable> {
+    check_column(&column)?;
+    let start = date_to_unix_seconds(start)?;
+    let end = date_to_unix_seconds(end)?;
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw {
+      sql: format!("{column} BETWEEN ? AND ?"),
+      params: vec![
+        rusqlite::types::Value::Integer(start),
+        rusqlite::types::Value::Integer(end),
+      ],
+    });
+    Ok(filtered)
+  }
 
-        if let Some((ref col, ref dir)) = self.order_by {
-            sql.push_str(&format!(" ORDER BY {} {}", col, dir));
+  // Rows whose JSON array at `path` (e.g. `$.tags`, or `$` for the whole column) holds `value`
+  #[napi]
+  pub fn where_json_contains(
+    &self,
+    column: String,
+    path: String,
+    value: WhereValue,
+  ) -> Result<FilteredTable> {
+    check_column(&column)?;
+    let path = if path.starts_with('$') {
+      path
+    } else {
+      format!("$.{}", path)
+    };
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Raw {
+      sql: format!("EXISTS (SELECT 1 FROM json_each({column}, ?) WHERE value = ?)"),
+      params: vec![rusqlite::types::Value::Text(path), where_value(value)],
+    });
+    Ok(filtered)
+  }
+
+  // JSON counterpart of `where({ ... })`: one `json_extract(column, path) = ?` per path, ANDed
+  #[napi]
+  pub fn where_json_match(&self, column: String, paths: JsObject) -> Result<FilteredTable> {
+    check_column(&column)?;
+    let mut filtered = self.clone();
+    let keys = paths.get_property_names()?;
+    for i in 0..keys.get_array_length()? {
+      let key = keys
+        .get_element::<JsString>(i)?
+        .into_utf8()?
+        .as_str()?
+        .to_owned();
+      let value = js_unknown_to_rusqlite_value(
+        paths.get_named_property::<JsUnknown>(&key)?,
+        &self.table.config,
+      )?;
+      let path = if key.starts_with('$') {
+        key
+      } else {
+        format!("$.{}", key)
+      };
+      let (sql, params) = match value {
+        rusqlite::types::Value::Null => (
+          format!("json_extract({column}, ?) IS NULL"),
+          vec![rusqlite::types::Value::Text(path)],
+        ),
+        value => (
+          format!("json_extract({column}, ?) = ?"),
+          vec![rusqlite::types::Value::Text(path), value],
+        ),
+      };
+      filtered.conditions.push(Condition::Raw { sql, params });
+    }
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn where_not(
+    &self,
+    env: Env,
+    column_or_group: napi::Either<String, JsFunction>,
+    op_or_value: Option<napi::Either<String, WhereValue>>,
+    value_opt: Option<WhereValue>,
+  ) -> Result<FilteredTable> {
+    let negated = match column_or_group {
+      napi::Either::A(column) => {
+        check_column(&column)?;
+        let op_or_value =
+          op_or_value.ok_or_else(|| napi::Error::from_reason("Invalid arguments for whereNot"))?;
+        let (operator, value) =
+          parse_where_args(op_or_value, value_opt, self.table.config.default_operator)?;
+        Condition::Basic {
+          column,
+          operator,
+          value,
         }
+      }
+      napi::Either::B(callback) => {
+        let group = FilteredTable::new(self.table.clone()).into_instance(env)?;
+        let returned = callback.call(None, &[group.as_object(env)])?;
+        let conditions = if FilteredTable::instance_of(env, &returned)? {
+          let returned =
+            unsafe { ClassInstance::<FilteredTable>::from_napi_value(env.raw(), returned.raw())? };
+          returned.conditions.clone()
+        } else {
+          group.conditions.clone()
+        };
+        if conditions.is_empty() {
+          return Ok(self.clone());
+        }
+        Condition::Group(conditions)
+      }
+    };
+
+    let mut filtered = self.clone();
+    filtered.conditions.push(Condition::Not(Box::new(negated)));
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn has(&self, relation: String) -> Result<FilteredTable> {
+    let mut filtered = self.clone();
+    filtered.conditions.push(self.relation_exists(&relation)?);
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn doesnt_have(&self, relation: String) -> Result<FilteredTable> {
+    let mut filtered = self.clone();
+    filtered
+      .conditions
+      .push(Condition::Not(Box::new(self.relation_exists(&relation)?)));
+    Ok(filtered)
+  }
+
+  fn relation_exists(&self, name: &str) -> Result<Condition> {
+    let relation = self.table.relation(name)?;
+    Ok(Condition::Raw {
+      sql: format!(
+        "EXISTS (SELECT 1 FROM {related} WHERE {related}.{} = {}.{})",
+        quote_identifier(&relation.related_column),
+        self.table.reference(),
+        quote_identifier(&relation.local_column),
+        related = quote_table_name(&relation.table),
+      ),
+      params: vec![],
+    })
+  }
+
+  #[napi]
+  pub fn with_json(&self, relation: String) -> Result<FilteredTable> {
+    let related = self.table.relation(&relation)?;
+
+    let columns = {
+      let conn = self.table.lock()?;
+      table_columns(&self.table.schema, &conn, &related.table)?
+    };
+    if columns.is_empty() {
+      return Err(napi::Error::from_reason(format!(
+        "Related table {} has no columns",
+        related.table
+      )));
+    }
+
+    // Aliased so a self-referencing relation still correlates with the outer row
+    let fields = columns
+      .iter()
+      .map(|c| {
+        format!(
+          "{}, __related.{}",
+          sql_literal(&rusqlite::types::Value::Text(c.clone())),
+          quote_identifier(c)
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+    let subquery = format!(
+            "SELECT json_group_array(json_object({fields})) FROM {} AS __related WHERE __related.{} = {}.{}",
+            quote_table_name(&related.table),
+            quote_identifier(&related.related_column),
+            self.table.reference(),
+            quote_identifier(&related.local_column),
+        );
+
+    let mut filtered = self.clone();
+    filtered
+      .json_relations
+      .retain(|(name, _)| *name != relation);
+    filtered.json_relations.push((relation, subquery));
+    Ok(filtered)
+  }
+
+  #[napi]
+  pub fn get(&self, env: Env) -> Result<Vec<JsObject>> {
+    self.all(env)
+  }
+
+  pub(crate) fn predicate(&self, params: &mut Vec<rusqlite::types::Value>) -> Option<String> {
+    if self.conditions.is_empty() {
+      return None;
+    }
+
+    let clauses: Vec<String> = self.conditions.iter().map(|c| c.to_sql(params)).collect();
+    Some(clauses.join(" AND "))
+  }
+
+  pub(crate) fn build_conditions(
+    &self,
+    sql: &mut String,
+    params: &mut Vec<rusqlite::types::Value>,
+  ) {
+    if let Some(predicate) = self.predicate(params) {
+      sql.push_str(" WHERE ");
+      sql.push_str(&predicate);
+    }
+  }
+
+  fn build_write_conditions(
+    &self,
+    conn: &rusqlite::Connection,
+    sql: &mut String,
+    params: &mut Vec<rusqlite::types::Value>,
+    limit: Option<i64>,
+    returning: bool,
+  ) -> Result<()> {
+    let returning_clause = if returning { " RETURNING *" } else { "" };
+
+    let Some(limit) = limit else {
+      self.build_conditions(sql, params);
+      sql.push_str(returning_clause);
+      return Ok(());
+    };
+    if limit < 1 {
+      return Err(napi::Error::from_reason("Limit must be a positive integer"));
+    }
+
+    let order_clause = self
+      .order_by
+      .as_ref()
+      .map(|(col, dir)| format!(" ORDER BY {} {}", col, dir))
+      .unwrap_or_default();
+
+    if supports_update_delete_limit(conn) {
+      self.build_conditions(sql, params);
+      sql.push_str(&format!("{returning_clause}{order_clause} LIMIT ?"));
+    } else {
+      // Emulate `... LIMIT n` for builds without SQLITE_ENABLE_UPDATE_DELETE_LIMIT
+      sql.push_str(&format!(
+        " WHERE rowid IN (SELECT rowid FROM {}",
+        self.table.table_sql()
+      ));
+      self.build_conditions(sql, params);
+      sql.push_str(&format!("{order_clause} LIMIT ?){returning_clause}"));
+    }
+    params.push(rusqlite::types::Value::Integer(limit));
+    Ok(())
+  }
+
+  fn execute_write(
+    &self,
+    env: Env,
+    conn: &rusqlite::Connection,
+    query: Segment,
+    returning: bool,
+    result: bool,
+    debug: bool,
+  ) -> Result<WriteResult> {
+    let Segment { sql, params } = query;
+    if !returning {
+      let bindings = debug.then(|| bindings_to_js(env, &params)).transpose()?;
+      let changes = conn
+        .execute(&sql, rusqlite::params_from_iter(params))
+        .map_err(|e| napi::Error::from_reason(format!("Execute failed: {}", e)))?;
+      if !result {
+        return Ok(Either3::A(changes as i64));
+      }
+      return Ok(Either3::C(RunResult {
+        changes: changes as i64,
+        last_insert_rowid: conn.last_insert_rowid(),
+        sql: debug.then(|| sql.clone()),
+        bindings,
+        ignored: None,
+      }));
+    }
+
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(format!("Prepare failed: {}", e)))?;
+    let column_names = stmt
+      .column_names()
+      .iter()
+      .map(|s| s.to_string())
+      .collect::<Vec<_>>();
+    let rows = stmt
+      .query_map(rusqlite::params_from_iter(params), |row| {
+        row_to_object(env, row, &column_names, &self.table.config)
+      })
+      .map_err(|e| napi::Error::from_reason(format!("Execute failed: {}", e)))?;
+
+    rows
+      .map(|res| res.map_err(|e| napi::Error::from_reason(format!("Row failed: {}", e))))
+      .collect::<Result<Vec<_>>>()
+      .map(Either3::B)
+  }
+
+  #[napi]
+  pub fn as_array(&self, env: Env) -> Result<JsObject> {
+    let (sql, params) = self.select_sql();
+    let conn = self.table.lock()?;
+    query_to_arrays(
+      env,
+      &conn,
+      &sql,
+      SqlParams::Positional(params.into_iter().map(BindValue::Value).collect()),
+      &self.table.config,
+    )
+  }
+
+  fn select_sql(&self) -> (String, Vec<rusqlite::types::Value>) {
+    let mut columns = Segment::new(
+      self
+        .columns
+        .as_ref()
+        .map_or_else(|| "*".to_string(), |columns| columns.join(", ")),
+      vec![],
+    );
+    for (name, subquery) in &self.json_relations {
+      columns.push_sql(&format!(", ({}) AS {}", subquery, quote_identifier(name)));
+    }
+    columns.push_joined(", ", &self.raw_columns, ", ");
+    self.with_window(self.select_columns_sql(columns))
+  }
+
+  // Row selects get the LIMIT/OFFSET window; aggregates built on select_columns_sql don't. An
+  // offset alone needs `LIMIT -1`, since SQLite has no bare OFFSET.
+  fn with_window(
+    &self,
+    (mut sql, mut params): (String, Vec<rusqlite::types::Value>),
+  ) -> (String, Vec<rusqlite::types::Value>) {
+    if self.limit.is_none() && self.offset.is_none() {
+      return (sql, params);
+    }
+    sql.push_str(" LIMIT ?");
+    params.push(rusqlite::types::Value::Integer(self.limit.unwrap_or(-1)));
+    if let Some(offset) = self.offset {
+      sql.push_str(" OFFSET ?");
+      params.push(rusqlite::types::Value::Integer(offset));
+    }
+    (sql, params)
+  }
+
+  fn select_columns_sql(&self, columns: Segment) -> (String, Vec<rusqlite::types::Value>) {
+    let mut query = Segment::new(format!("{}SELECT ", self.table.with_clause()), vec![]);
+    query.push(&columns);
+    query.push_sql(&format!(" FROM {}", self.table.table_sql()));
+    self.build_conditions(&mut query.sql, &mut query.params);
+
+    if !self.group_by.is_empty() {
+      query.push_sql(&format!(" GROUP BY {}", self.group_by.join(", ")));
+    }
+    query.push_joined(" HAVING ", &self.having, " AND ");
+
+    let mut order = Vec::new();
+    if let Some((ref col, ref dir)) = self.order_by {
+      order.push(Segment::new(format!("{} {}", col, dir), vec![]));
+    }
+    order.extend(self.raw_order.iter().cloned());
+    query.push_joined(" ORDER BY ", &order, ", ");
+
+    (query.sql, query.params)
+  }
+
+  #[napi]
+  pub fn all(&self, env: Env) -> Result<Vec<JsObject>> {
+    let rows = self.fetch_rows(env)?;
+    parse_json_columns(env, rows, &self.json_relation_names())
+  }
 
-        let conn = self.table.conn.lock().map_err(|e| napi::Error::from_reason(format!("Lock poisoned: {}", e)))?;
-        let mut stmt = conn.prepare(&sql)
-            .map_err(|e| napi::Error::from_reason(format!("Prepare failed: {}", e)))?;
-
-        let column_names = stmt
-            .column_names()
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>();
-
-        let rows = stmt
-            .query_map(rusqlite::params_from_iter(params), |row| {
-                row_to_object(env, row, &column_names)
-            })
-            .map_err(|e| napi::Error::from_reason(format!("Query failed: {}", e)))?;
-
-        rows.map(|res| res.map_err(|e| napi::Error::from_reason(format!("Row failed: {}", e))))
-            .collect()
-    }
-
-    #[napi]
-    pub fn destroy(&self) -> Result<()> {
-        let mut sql = format!("DELETE FROM {} WHERE ", self.table.name);
-        let mut params = Vec::new();
-        self.build_conditions(&mut sql, &mut params);
-
-        let conn = self.table.conn.lock().map_err(|e| napi::Error::from_reason(format!("Lock poisoned: {}", e)))?;
-        conn.execute(&sql, rusqlite::params_from_iter(params))
-            .map_err(|e| napi::Error::from_reason(format!("Execute failed: {}", e)))?;
-        Ok(())
-    }
-
-    #[napi]
-    pub fn update(&self, data: JsObject) -> Result<()> {
-        let conn = self.table.conn.lock().map_err(|e| napi::Error::from_reason(format!("Lock poisoned: {}", e)))?;
-
-        let props = data.get_property_names()?;
-        let mut keys = Vec::new();
-        let mut values = Vec::new();
-        let mut placeholders = Vec::new();
-
-        for i in 0..props.get_array_length()? {
-            let key = props.get_element::<JsString>(i)?.into_utf8()?.as_str()?.to_owned();
-            let value = data.get_named_property::<JsUnknown>(&key)?;
-            let val = match value.get_type()? {
-                ValueType::String => rusqlite::types::Value::Text(
-                    value.coerce_to_string()?.into_utf8()?.as_str()?.to_string(),
-                ),
-                ValueType::Number => rusqlite::types::Value::Real(
-                    value.coerce_to_number()?.get_double()?,
-                ),
-                ValueType::Boolean => rusqlite::types::Value::Integer(
-                    value.coerce_to_bool()?.get_value()? as i64,
-                ),
-                _ => return Err(napi::Error::from_reason("Unsupported value type in update")),
+  // `all()` with the query run on the libuv threadpool, see Database::queryAsync
+  #[napi(ts_return_type = "Promise<object[]>")]
+  pub fn all_async(
+    &self,
+    env: Env,
+    options: Option<AsyncQueryOptions>,
+  ) -> Result<AsyncTask<QueryTask>> {
+    let (sql, params) = self.select_sql();
+    let task = QueryTask::new(
+      env,
+      (
+        self.table.conn.clone(),
+        self.table.interrupt.clone(),
+        self.table.closed.clone(),
+      ),
+      self.table.cache.clone(),
+      self.table.config.clone(),
+      (sql, OwnedParams::Positional(params)),
+      self.json_relation_names(),
+      options.and_then(|o| o.signal),
+    )?;
+    Ok(AsyncTask::new(task))
+  }
+
+  fn json_relation_names(&self) -> Vec<String> {
+    self
+      .json_relations
+      .iter()
+      .map(|(name, _)| name.clone())
+      .collect()
+  }
+
+  fn fetch_rows(&self, env: Env) -> Result<Vec<JsObject>> {
+    let (sql, params) = self.select_sql();
+
+    let conn = self.table.lock()?;
+    if let Some(cache) = &self.table.cache {
+      let params = SqlParams::Positional(params.into_iter().map(BindValue::Value).collect());
+      let cached = cached_query(cache, &conn, &sql, params, false)?;
+      return cached_rows_to_objects(env, &cached, &self.table.config);
+    }
+
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(format!("Prepare failed: {}", e)))?;
+
+    let column_names = stmt
+      .column_names()
+      .iter()
+      .map(|s| s.to_string())
+      .collect::<Vec<_>>();
+
+    let keys = column_keys(env, &column_names)?;
+
+    let rows = stmt
+      .query_map(rusqlite::params_from_iter(params), |row| {
+        row_to_keyed_object(env, row, &keys, &self.table.config)
+      })
+      .map_err(|e| napi::Error::from_reason(format!("Query failed: {}", e)))?;
+
+    // With a LIMIT the result can't outgrow it, so the rows are collected into a vector sized
+    // once up front (capped, as a limit is often far larger than the table)
+    let capacity = self
+      .limit
+      .map_or(0, |limit| limit.clamp(0, PREALLOCATED_ROWS_MAX) as usize);
+    let mut objects = Vec::with_capacity(capacity);
+    for row in rows {
+      objects.push(row.map_err(|e| napi::Error::from_reason(format!("Row failed: {}", e)))?);
+    }
+    Ok(objects)
+  }
+
+  // Folds the rows through `reducer(acc, row, index)` straight off the statement, so only the
+  // accumulator outlives each batch of rows. The connection stays busy until the last row, so the
+  // reducer must not use this database.
+  #[napi]
+  pub fn reduce(&self, env: Env, initial: JsUnknown, reducer: JsFunction) -> Result<JsUnknown> {
+    const ROWS_PER_SCOPE: usize = 1000;
+
+    let (sql, params) = self.select_sql();
+    let conn = self.table.lock()?;
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(format!("Prepare failed: {}", e)))?;
+    let column_names = stmt
+      .column_names()
+      .iter()
+      .map(|s| s.to_string())
+      .collect::<Vec<_>>();
+    let mut rows = stmt
+      .query(rusqlite::params_from_iter(params))
+      .map_err(|e| napi::Error::from_reason(format!("Query failed: {}", e)))?;
+
+    // Each batch runs in its own handle scope so the row objects can be collected; the
+    // accumulator is carried between batches on a referenced holder object (primitives can't
+    // be referenced directly)
+    let mut holder = env.create_object()?;
+    holder.set_named_property("value", initial)?;
+    let mut holder = env.create_reference(holder)?;
+    let mut index = 0u32;
+    let mut done = false;
+    let folded = (|| {
+      while !done {
+        env.run_in_scope(|| {
+          let keys = column_keys(env, &column_names)?;
+          let mut holder = env.get_reference_value::<JsObject>(&holder)?;
+          let mut value = holder.get_named_property::<JsUnknown>("value")?;
+          for _ in 0..ROWS_PER_SCOPE {
+            let row = match rows
+              .next()
+              .map_err(|e| napi::Error::from_reason(format!("Row failed: {}", e)))?
+            {
+              Some(row) => row,
+              None => {
+                done = true;
+                break;
+              }
             };
+            let object = row_to_keyed_object(env, row, &keys, &self.table.config)
+              .map_err(|e| napi::Error::from_reason(format!("Row failed: {}", e)))?;
+            value = reducer.call(
+              None,
+              &[
+                value,
+                object.into_unknown(),
+                env.create_uint32(index)?.into_unknown(),
+              ],
+            )?;
+            index += 1;
+          }
+          holder.set_named_property("value", value)
+        })?;
+      }
+      env
+        .get_reference_value::<JsObject>(&holder)?
+        .get_named_property::<JsUnknown>("value")
+    })();
+    holder.unref(env)?;
+    let result = folded?;
+    Ok(result)
+  }
 
-            keys.push(key);
-            values.push(val);
-            placeholders.push("?");
+  #[napi]
+  pub fn to_csv(&self) -> Result<String> {
+    let mut csv = String::new();
+    self.write_csv(usize::MAX, |chunk| {
+      csv.push_str(&chunk);
+      Ok(())
+    })?;
+    Ok(csv)
+  }
+
+  // Passes the CSV to `callback` in chunks of `chunkRows` rows (the first one led by the
+  // header) while reading, so the whole result is never held in memory. The connection
+  // stays busy until the last chunk, so the callback must not use this database.
+  #[napi]
+  pub fn stream_csv(&self, callback: JsFunction, options: Option<CsvOptions>) -> Result<i64> {
+    let chunk_rows = options.and_then(|o| o.chunk_rows).unwrap_or(1000);
+    if chunk_rows == 0 {
+      return Err(napi::Error::from_reason("chunkRows must be at least 1"));
+    }
+    self.write_csv(chunk_rows as usize, |chunk| {
+      callback.call1::<String, JsUnknown>(chunk)?;
+      Ok(())
+    })
+  }
+
+  fn write_csv(
+    &self,
+    chunk_rows: usize,
+    mut emit: impl FnMut(String) -> Result<()>,
+  ) -> Result<i64> {
+    let (sql, params) = self.select_sql();
+    let conn = self.table.lock()?;
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(format!("Prepare failed: {}", e)))?;
+
+    let mut chunk = String::new();
+    for (i, name) in stmt.column_names().iter().enumerate() {
+      if i > 0 {
+        chunk.push(',');
+      }
+      csv_field(&rusqlite::types::Value::Text(name.to_string()), &mut chunk);
+    }
+    chunk.push('\n');
+
+    let column_count = stmt.column_count();
+    let mut rows = stmt
+      .query(rusqlite::params_from_iter(params))
+      .map_err(|e| napi::Error::from_reason(format!("Query failed: {}", e)))?;
+    let mut written = 0i64;
+    let mut pending = 0;
+    while let Some(row) = rows
+      .next()
+      .map_err(|e| napi::Error::from_reason(format!("Row failed: {}", e)))?
+    {
+      for i in 0..column_count {
+        if i > 0 {
+          chunk.push(',');
         }
+        let value = row
+          .get::<_, rusqlite::types::Value>(i)
+          .map_err(|e| napi::Error::from_reason(format!("Row failed: {}", e)))?;
+        csv_field(&value, &mut chunk);
+      }
+      chunk.push('\n');
+      written += 1;
+      pending += 1;
+      if pending == chunk_rows {
+        emit(std::mem::take(&mut chunk))?;
+        pending = 0;
+      }
+    }
+    // The header always goes out, even for an empty result
+    if !chunk.is_empty() {
+      emit(chunk)?;
+    }
+    Ok(written)
+  }
+
+  // Counts the rows `all()` would return
+  #[napi]
+  pub fn count(&self) -> Result<napi::Either<i64, BigInt>> {
+    self
+      .aggregate("COUNT(*)")
+      .map(|count| count_to_js(count, &self.table.config))
+  }
+
+  // SUM/AVG/MIN/MAX over the matching rows; None when nothing matches (SQLite yields NULL)
+  #[napi]
+  pub fn sum(&self, column: String) -> Result<Option<f64>> {
+    self.aggregate_column("SUM", &column)
+  }
+
+  #[napi]
+  pub fn avg(&self, column: String) -> Result<Option<f64>> {
+    self.aggregate_column("AVG", &column)
+  }
+
+  #[napi]
+  pub fn min(&self, column: String) -> Result<Option<f64>> {
+    self.aggregate_column("MIN", &column)
+  }
+
+  #[napi]
+  pub fn max(&self, column: String) -> Result<Option<f64>> {
+    self.aggregate_column("MAX", &column)
+  }
 
-        let set_clause = keys.iter().zip(placeholders.iter())
-            .map(|(k, p)| format!("{k} = {p}"))
-            .collect::<Vec<_>>()
-            .join(", ");
+  fn aggregate_column(&self, function: &str, column: &str) -> Result<Option<f64>> {
+    // The column is interpolated, not bound, and left unquoted so an unknown name fails as
+    // "no such column" instead of aggregating a string literal
+    check_column(column)?;
+    self.aggregate(&format!("{}({})", function, column))
+  }
 
-        let mut sql = format!("UPDATE {} SET {} WHERE ", self.table.name, set_clause);
-        let mut where_params = Vec::new();
-        self.build_conditions(&mut sql, &mut where_params);
+  // A plain filter is a direct `SELECT expr FROM t WHERE ...` (no WHERE at all without
+  // conditions); grouped or paged queries are wrapped so the aggregate runs over the groups, or
+  // the rows inside the window.
+  fn aggregate<T: rusqlite::types::FromSql>(&self, expression: &str) -> Result<T> {
+    let (sql, params) = if self.group_by.is_empty()
+      && self.having.is_empty()
+      && self.limit.is_none()
+      && self.offset.is_none()
+    {
+      let mut filtered = self.clone();
+      filtered.order_by = None;
+      filtered.raw_order.clear();
+      filtered.select_columns_sql(Segment::new(expression, vec![]))
+    } else {
+      let (sql, params) = self.select_sql();
+      (format!("SELECT {} FROM ({})", expression, sql), params)
+    };
+    let conn = self.table.lock()?;
+    conn
+      .query_row(&sql, rusqlite::params_from_iter(params), |row| row.get(0))
+      .map_err(|e| napi::Error::from_reason(format!("Query failed: {}", e)))
+  }
 
-        values.extend(where_params);
-        conn.execute(&sql, rusqlite::params_from_iter(values))
-            .map_err(|e| napi::Error::from_reason(format!("Execute failed: {}", e)))?;
-        Ok(())
+  #[napi]
+  pub fn count_distinct(&self, column: String) -> Result<napi::Either<i64, BigInt>> {
+    check_column(&column)?;
+    self
+      .aggregate(&format!("COUNT(DISTINCT {})", column))
+      .map(|count| count_to_js(count, &self.table.config))
+  }
+
+  // SELECT ... FOR UPDATE, SQLite style: there are no row locks, so this takes the write lock
+  // on the whole database (with a no-op DELETE, which also upgrades a deferred transaction)
+  // and then reads the rows. Other connections can't write until the transaction ends.
+  #[napi]
+  pub fn lock_for_update(&self, env: Env) -> Result<Vec<JsObject>> {
+    {
+      let conn = self.table.lock()?;
+      if conn.is_autocommit() {
+        return Err(napi::Error::from_reason(
+                    "lockForUpdate must run inside a transaction, e.g. begin('immediate') or transaction(fn, 'immediate')",
+                ));
+      }
+      conn
+        .execute(&format!("DELETE FROM {} WHERE 0", self.table.name), [])
+        .map_err(|e| napi::Error::from_reason(format!("Failed to take the write lock: {}", e)))?;
+    }
+    // Bypasses the result cache; locked rows should be read fresh
+    let mut filtered = self.clone();
+    filtered.table.cache = None;
+    filtered.all(env)
+  }
+
+  #[napi]
+  pub fn destroy(&self, env: Env, options: Option<WriteOptions>) -> Result<WriteResult> {
+    let (limit, returning, result, debug) = parse_write_options(options)?;
+    let conn = self.table.lock()?;
+
+    let mut sql = format!("DELETE FROM {}", self.table.table_sql());
+    let mut params = Vec::new();
+    self.build_write_conditions(&conn, &mut sql, &mut params, limit, returning)?;
+
+    self.execute_write(
+      env,
+      &conn,
+      Segment::new(sql, params),
+      returning,
+      result,
+      debug,
+    )
+  }
+
+  #[napi]
+  pub fn replace(&self, env: Env, data: JsObject) -> Result<()> {
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+    for (col, val) in js_object_to_hashmap(&env, &data, None)? {
+      columns.push(col);
+      values.push(js_unknown_to_rusqlite_value(val, &self.table.config)?);
+    }
+
+    // Equality filters describe the identity of the replaced row, so carry them over
+    for condition in &self.conditions {
+      let Condition::Basic {
+        column,
+        operator,
+        value,
+      } = condition
+      else {
+        continue;
+      };
+      if !(operator == "=" || operator.eq_ignore_ascii_case("IS")) || columns.contains(column) {
+        continue;
+      }
+      columns.push(column.clone());
+      values.push(value.clone());
+    }
+
+    let mut conn = self.table.lock()?;
+    let tx = conn
+      .savepoint()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    let mut sql = format!("DELETE FROM {}", self.table.table_sql());
+    let mut params = Vec::new();
+    self.build_conditions(&mut sql, &mut params);
+    tx.execute(&sql, rusqlite::params_from_iter(params))
+      .map_err(|e| {
+        napi::Error::from_reason(format!(
+          "Replace failed while deleting the matching rows: {}",
+          e
+        ))
+      })?;
+
+    let sql = if columns.is_empty() {
+      format!("INSERT INTO {} DEFAULT VALUES", self.table.name)
+    } else {
+      format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        self.table.name,
+        columns
+          .iter()
+          .map(|c| quote_identifier(c))
+          .collect::<Vec<_>>()
+          .join(", "),
+        vec!["?"; columns.len()].join(", ")
+      )
+    };
+    tx.execute(&sql, rusqlite::params_from_iter(values))
+      .map_err(|e| {
+        napi::Error::from_reason(format!(
+          "Replace failed while inserting the replacement row: {}",
+          e
+        ))
+      })?;
+
+    tx.commit()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    Ok(())
+  }
+
+  #[napi]
+  pub fn update_raw(&self, set_fragment: String, bindings: Option<Vec<JsUnknown>>) -> Result<()> {
+    let set = self.raw_segment(set_fragment, bindings)?;
+
+    let conn = self.table.lock()?;
+
+    // SET bindings come first, followed by the WHERE bindings
+    let mut query = Segment::new(format!("UPDATE {} SET ", self.table.table_sql()), vec![]);
+    query.push(&set);
+    self.build_conditions(&mut query.sql, &mut query.params);
+
+    conn
+      .execute(&query.sql, rusqlite::params_from_iter(query.params))
+      .map_err(|e| napi::Error::from_reason(format!("Execute failed: {}", e)))?;
+    Ok(())
+  }
+
+  #[napi]
+  pub fn update(
+    &self,
+    env: Env,
+    data: JsObject,
+    options: Option<WriteOptions>,
+  ) -> Result<WriteResult> {
+    let (limit, returning, result, debug) = parse_write_options(options)?;
+    let conn = self.table.lock()?;
+
+    let props = data.get_property_names()?;
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    let mut placeholders = Vec::new();
+
+    for i in 0..props.get_array_length()? {
+      let key = props
+        .get_element::<JsString>(i)?
+        .into_utf8()?
+        .as_str()?
+        .to_owned();
+      let value = data.get_named_property::<JsUnknown>(&key)?;
+      // Bound the same way insert binds them (integers stay integers, Dates follow dateFormat)
+      match value.get_type()? {
+        ValueType::Undefined => continue,
+        ValueType::Null
+        | ValueType::Boolean
+        | ValueType::Number
+        | ValueType::BigInt
+        | ValueType::String => {}
+        ValueType::Object if value.is_date()? || value.is_buffer()? => {}
+        _ => return Err(napi::Error::from_reason("Unsupported value type in update")),
+      }
+      let val = js_unknown_to_rusqlite_value(value, &self.table.config)?;
+
+      // A key listed twice (e.g. by a Proxy) sets the column once, the last value winning
+      if let Some(pos) = keys.iter().position(|k| *k == key) {
+        values[pos] = val;
+        continue;
+      }
+      keys.push(key);
+      values.push(val);
+      placeholders.push("?");
+    }
+
+    if keys.is_empty() {
+      if result {
+        return Ok(Either3::C(RunResult {
+          changes: 0,
+          last_insert_rowid: conn.last_insert_rowid(),
+          sql: None,
+          bindings: None,
+          ignored: None,
+        }));
+      }
+      return Ok(if returning {
+        Either3::B(Vec::new())
+      } else {
+        Either3::A(0)
+      });
     }
+
+    let set_clause = keys
+      .iter()
+      .zip(placeholders.iter())
+      .map(|(k, p)| format!("{} = {p}", quote_identifier(k)))
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    let mut sql = format!("UPDATE {} SET {}", self.table.table_sql(), set_clause);
+    let mut where_params = Vec::new();
+    self.build_write_conditions(&conn, &mut sql, &mut where_params, limit, returning)?;
+
+    values.extend(where_params);
+    self.execute_write(
+      env,
+      &conn,
+      Segment::new(sql, values),
+      returning,
+      result,
+      debug,
+    )
+  }
 }
@@ -1,4 +1,6 @@
+mod cache;
 mod database;
-mod table;
+mod extra;
 mod filtered_table;
-mod extra;
\ No newline at end of file
+mod table;
+mod task;
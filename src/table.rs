@@ -1,213 +1,1365 @@
-use napi::{Env, JsObject, JsUnknown, Result};
+use napi::bindgen_prelude::{AsyncTask, BigInt, ClassInstance, Either3, FromNapiValue};
+use napi::{Env, JsFunction, JsObject, JsUnknown, NapiRaw, Result, ValueType};
 use napi_derive::napi;
-use rusqlite::{Connection};
-use std::sync::{Arc, Mutex};
+use rusqlite::{Connection, InterruptHandle};
 use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, MutexGuard};
 
-use crate::extra::{js_object_to_hashmap, js_unknown_to_rusqlite_value};
-use crate::filtered_table::{FilteredTable};
+use crate::cache::{table_column_meta, table_columns, SchemaCache, SharedCache};
+use crate::extra::{
+  bindings_to_js, id_to_value, inline_params, is_valid_qualified_identifier, js_object_to_hashmap,
+  js_unknown_to_bind_value, js_unknown_to_rusqlite_value, js_unknown_to_typed_value,
+  lock_connection, quote_identifier, quote_table_name, row_to_object, split_table_name,
+  validate_type_spec, value_key, values_equal, BindValue, Config,
+};
+use crate::filtered_table::{
+  where_value, CsvOptions, FilteredTable, Page, PaginateOptions, WhereValue, WriteOptions,
+  WriteResult,
+};
+use crate::task::{AsyncQueryOptions, QueryTask};
+
+#[napi(object)]
+pub struct InsertOptions {
+  pub types: Option<HashMap<String, String>>,
+  pub json_columns: Option<Vec<String>>,
+  pub result: Option<bool>,
+  pub debug: Option<bool>,
+  pub ignore_unknown: Option<bool>,
+  // INSERT OR IGNORE: rows violating a constraint are skipped instead of failing the batch
+  pub or_ignore: Option<bool>,
+}
+
+#[napi(object)]
+pub struct IndexOptions {
+  pub name: Option<String>,
+  pub unique: Option<bool>,
+  // Partial index predicate: a raw SQL fragment or a filtered query on this table
+  #[napi(js_name = "where")]
+  pub where_clause: Option<JsUnknown>,
+}
+
+#[napi(object)]
+pub struct UpsertOptions {
+  // Condition on the DO UPDATE, e.g. `excluded.updated_at > updated_at`; rows failing it are
+  // left alone
+  #[napi(js_name = "where")]
+  pub where_clause: Option<String>,
+  pub bindings: Option<Vec<JsUnknown>>,
+}
+
+#[napi(object)]
+pub struct RunResult {
+  pub changes: i64,
+  pub last_insert_rowid: i64,
+  // Only filled in when the `debug` option is set
+  pub sql: Option<String>,
+  pub bindings: Option<Vec<JsUnknown>>,
+  // Rows skipped by an `orIgnore` insert
+  pub ignored: Option<i64>,
+}
+
+#[napi(object)]
+pub struct ColumnInfo {
+  pub name: String,
+  // 'virtual' or 'stored' for generated columns
+  pub generated: Option<String>,
+}
+
+#[napi(object)]
+pub struct SyncResult {
+  pub inserted: i64,
+  pub updated: i64,
+  pub deleted: i64,
+}
+
+#[derive(Clone)]
+pub(crate) struct Relation {
+  pub(crate) name: String,
+  pub(crate) table: String,
+  // Column on the related table matched against `local_column` on this table
+  pub(crate) related_column: String,
+  pub(crate) local_column: String,
+}
+
+pub(crate) type Relations = Arc<Mutex<HashMap<String, Vec<Relation>>>>;
+
+// Drops a table made by `withTempRows` once the last handle on it is gone
+pub(crate) struct TempTable {
+  pub(crate) conn: Arc<Mutex<Connection>>,
+  pub(crate) name: String,
+}
+
+impl Drop for TempTable {
+  fn drop(&mut self) {
+    // Finalizers can run while a call holds the lock; the table then lives until the
+    // connection closes, like every TEMP table
+    if let Ok(conn) = self.conn.try_lock() {
+      let _ = conn.execute_batch(&format!(
+        "DROP TABLE IF EXISTS temp.{}",
+        quote_identifier(&self.name)
+      ));
+    }
+  }
+}
 
 #[napi]
 pub struct Table {
-    pub(crate) name: String,
-    pub(crate) conn: Arc<Mutex<Connection>>,
+  pub(crate) name: String,
+  pub(crate) conn: Arc<Mutex<Connection>>,
+  pub(crate) interrupt: Arc<InterruptHandle>,
+  // Shared by every handle on the connection, set once it is closed
+  pub(crate) closed: Arc<AtomicBool>,
+  pub(crate) config: Config,
+  pub(crate) relations: Relations,
+  // Common table expressions prefixed to the SELECTs built from this table
+  pub(crate) ctes: Vec<(String, String)>,
+  pub(crate) cache: SharedCache,
+  pub(crate) schema: SchemaCache,
+  // Set by `as`; the generated SELECTs, UPDATEs and DELETEs refer to the rows by it
+  pub(crate) alias: Option<String>,
+  pub(crate) temp: Option<Arc<TempTable>>,
+}
+
+impl Table {
+  pub(crate) fn table_sql(&self) -> String {
+    match &self.alias {
+      Some(alias) => format!(
+        "{} AS {}",
+        quote_table_name(&self.name),
+        quote_identifier(alias)
+      ),
+      None => self.name.clone(),
+    }
+  }
+
+  // Qualifier for this table's columns in correlated subqueries, quoted
+  pub(crate) fn reference(&self) -> String {
+    self
+      .alias
+      .as_deref()
+      .map_or_else(|| quote_table_name(&self.name), quote_identifier)
+  }
+
+  pub(crate) fn lock(&self) -> Result<MutexGuard<'_, Connection>> {
+    lock_connection(&self.conn, &self.closed)
+  }
+
+  pub(crate) fn column_names(&self, conn: &Connection) -> Result<Vec<String>> {
+    table_columns(&self.schema, conn, &self.name)
+  }
+
+  // For columns spliced in double-quoted: an unknown quoted name would quietly read as a
+  // string literal rather than fail
+  pub(crate) fn check_known_column(&self, conn: &Connection, column: &str) -> Result<()> {
+    if self.is_cte()
+      || self
+        .column_names(conn)?
+        .iter()
+        .any(|c| c.eq_ignore_ascii_case(column))
+    {
+      Ok(())
+    } else {
+      Err(napi::Error::from_reason(format!(
+        "no such column: {}",
+        column
+      )))
+    }
+  }
+
+  pub(crate) fn is_cte(&self) -> bool {
+    self.ctes.iter().any(|(name, _)| *name == self.name)
+  }
+
+  pub(crate) fn with_clause(&self) -> String {
+    if self.ctes.is_empty() {
+      return String::new();
+    }
+    let ctes = self
+      .ctes
+      .iter()
+      .map(|(name, sql)| format!("{} AS ({})", quote_identifier(name), sql))
+      .collect::<Vec<_>>()
+      .join(", ");
+    format!("WITH {} ", ctes)
+  }
 }
 
 #[napi]
 impl Table {
-    #[napi]
-    pub fn first(&self, env: Env) -> Result<Option<JsObject>> {
-        FilteredTable {
-            table: self.clone(),
-            column: "1".to_string(),
-            operator: "=".to_string(),
-            value: napi::Either::B(1),
-            extra_conditions: vec![],
-            order_by: Some(("id".to_string(), "ASC".to_string())),
-        }.first(env)
-    }
-
-    #[napi]
-    pub fn last(&self, env: Env) -> Result<Option<JsObject>> {
-        FilteredTable {
-            table: self.clone(),
-            column: "1".to_string(),
-            operator: "=".to_string(),
-            value: napi::Either::B(1),
-            extra_conditions: vec![],
-            order_by: Some(("id".to_string(), "DESC".to_string())),
-        }.first(env)
-    }
-    
-    #[napi]
-    pub fn find(&self, env: Env, id: napi::Either<String, i64>) -> Result<Option<JsObject>> {
-        FilteredTable {
-            table: self.clone(),
-            column: "id".to_string(),
-            operator: "=".to_string(),
-            value: id,
-            extra_conditions: vec![],
-            order_by: None,
-        }.first(env)
-    }
-    
-    #[napi]
-    pub fn get(&self, env: Env) -> Result<Vec<JsObject>> {
-        self.all(env)
-    }
-
-    #[napi]
-    pub fn all(&self, env: Env) -> Result<Vec<JsObject>> {
-        FilteredTable {
-            table: self.clone(),
-            column: "1".to_string(),
-            operator: "=".to_string(),
-            value: napi::Either::B(1),
-            extra_conditions: vec![],
-            order_by: None,
-        }.all(env)
-    }
-
-    #[napi]
-    pub fn where_(
-        &self,
-        column: String,
-        op_or_value: napi::Either<String, napi::Either<String, i64>>,
-        value_opt: Option<napi::Either<String, i64>>,
-    ) -> Result<FilteredTable> {
-        let (operator, value) = if let Some(v) = value_opt {
-            let op = match op_or_value {
-                napi::Either::A(op) => op,
-                _ => "=".to_string(),
-            };
-            (op, v)
-        } else {
-            let val = match op_or_value {
-                napi::Either::B(v) => v,
-                _ => {
-                    return Err(napi::Error::from_reason(
-                        "Invalid arguments for where".to_string(),
-                    ))
-                }
-            };
-            ("=".to_string(), val)
-        };
-
-        Ok(FilteredTable {
-            table: self.clone(),
-            column,
-            operator,
-            value,
-            extra_conditions: vec![],
-            order_by: None,
+  #[napi]
+  pub fn first(&self, env: Env) -> Result<Option<JsObject>> {
+    FilteredTable {
+      order_by: Some(("id".to_string(), "ASC".to_string())),
+      ..FilteredTable::new(self.clone())
+    }
+    .first(env)
+  }
+
+  #[napi]
+  pub fn last(&self, env: Env) -> Result<Option<JsObject>> {
+    FilteredTable {
+      order_by: Some(("id".to_string(), "DESC".to_string())),
+      ..FilteredTable::new(self.clone())
+    }
+    .first(env)
+  }
+
+  #[napi]
+  pub fn find(&self, env: Env, id: napi::Either<String, i64>) -> Result<Option<JsObject>> {
+    FilteredTable::new(self.clone())
+      .with_condition("id", "=", id_to_value(id))
+      .first(env)
+  }
+
+  // SQLite storage class of one cell ('integer', 'real', 'text', 'blob' or 'null'), to see how a
+  // value was actually stored after type affinity; None when there's no row with that id
+  #[napi]
+  pub fn type_of(&self, id: napi::Either<String, i64>, column: String) -> Result<Option<String>> {
+    let sql = format!(
+      "{}SELECT typeof({}) FROM {} WHERE id = ?",
+      self.with_clause(),
+      quote_identifier(&column),
+      self.table_sql()
+    );
+    let conn = self.lock()?;
+    self.check_known_column(&conn, &column)?;
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    let mut rows = stmt
+      .query([id_to_value(id)])
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    match rows
+      .next()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?
+    {
+      Some(row) => row
+        .get(0)
+        .map(Some)
+        .map_err(|e| napi::Error::from_reason(e.to_string())),
+      None => Ok(None),
+    }
+  }
+
+  #[napi]
+  pub fn get_by(&self, env: Env, column: String, value: WhereValue) -> Result<Option<JsObject>> {
+    self.check_known_column(&*self.lock()?, &column)?;
+    FilteredTable::new(self.clone())
+      .with_condition(&quote_identifier(&column), "=", where_value(value))
+      .first(env)
+  }
+
+  #[napi]
+  pub fn get(&self, env: Env) -> Result<Vec<JsObject>> {
+    self.all(env)
+  }
+
+  #[napi]
+  pub fn all(&self, env: Env) -> Result<Vec<JsObject>> {
+    FilteredTable::new(self.clone()).all(env)
+  }
+
+  #[napi(ts_return_type = "Promise<object[]>")]
+  pub fn all_async(
+    &self,
+    env: Env,
+    options: Option<AsyncQueryOptions>,
+  ) -> Result<AsyncTask<QueryTask>> {
+    FilteredTable::new(self.clone()).all_async(env, options)
+  }
+
+  #[napi]
+  pub fn latest_per(
+    &self,
+    env: Env,
+    group_column: String,
+    order_column: String,
+  ) -> Result<Vec<JsObject>> {
+    let sql = format!(
+            "{}SELECT * FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY {} ORDER BY {} DESC) AS __row_number FROM {}) \
+             WHERE __row_number = 1",
+            self.with_clause(),
+            quote_identifier(&group_column),
+            quote_identifier(&order_column),
+            self.name
+        );
+
+    let conn = self.lock()?;
+    let mut stmt = conn
+      .prepare(&sql)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    // Drop the trailing __row_number helper column from the result objects
+    let mut column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    column_names.pop();
+
+    let rows = stmt
+      .query_map([], |row| {
+        row_to_object(env, row, &column_names, &self.config)
+      })
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    rows
+      .map(|res| res.map_err(|e| napi::Error::from_reason(e.to_string())))
+      .collect()
+  }
+
+  #[napi]
+  pub fn where_(
+    &self,
+    column_or_equalities: napi::Either<String, JsObject>,
+    op_or_value: Option<napi::Either<String, WhereValue>>,
+    value_opt: Option<WhereValue>,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_(column_or_equalities, op_or_value, value_opt)
+  }
+
+  #[napi]
+  pub fn select(&self, columns: Vec<String>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).select(columns)
+  }
+
+  #[napi]
+  pub fn as_(&self, alias: String) -> Result<Table> {
+    if alias.is_empty() {
+      return Err(napi::Error::from_reason("Table alias can't be empty"));
+    }
+    let mut table = self.clone();
+    table.alias = Some(alias);
+    Ok(table)
+  }
+
+  #[napi]
+  pub fn where_raw(&self, sql: String, bindings: Option<Vec<JsUnknown>>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_raw(sql, bindings)
+  }
+
+  #[napi]
+  pub fn select_raw(&self, sql: String, bindings: Option<Vec<JsUnknown>>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).select_raw(sql, bindings)
+  }
+
+  #[napi]
+  pub fn group_by(&self, columns: Vec<String>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).group_by(columns)
+  }
+
+  #[napi]
+  pub fn having_raw(&self, sql: String, bindings: Option<Vec<JsUnknown>>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).having_raw(sql, bindings)
+  }
+
+  #[napi]
+  pub fn order_by_raw(
+    &self,
+    sql: String,
+    bindings: Option<Vec<JsUnknown>>,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).order_by_raw(sql, bindings)
+  }
+
+  #[napi]
+  pub fn paginate(
+    &self,
+    env: Env,
+    page: i64,
+    per_page: i64,
+    options: Option<PaginateOptions>,
+  ) -> Result<Page> {
+    FilteredTable::new(self.clone()).paginate(env, page, per_page, options)
+  }
+
+  #[napi]
+  pub fn limit(&self, n: i64) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).limit(n)
+  }
+
+  #[napi]
+  pub fn offset(&self, n: i64) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).offset(n)
+  }
+
+  #[napi]
+  pub fn where_json_match(&self, column: String, paths: JsObject) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_json_match(column, paths)
+  }
+
+  #[napi]
+  pub fn where_enum(&self, column: String, value: String) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_enum(column, value)
+  }
+
+  #[napi]
+  pub fn where_starts_with(&self, column: String, prefix: String) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_starts_with(column, prefix)
+  }
+
+  #[napi]
+  pub fn where_ends_with(&self, column: String, suffix: String) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_ends_with(column, suffix)
+  }
+
+  #[napi]
+  pub fn where_in(&self, column: String, values: Vec<JsUnknown>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_in(column, values)
+  }
+
+  #[napi]
+  pub fn where_in_query(
+    &self,
+    column: String,
+    query: ClassInstance<FilteredTable>,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_in_query(column, query)
+  }
+
+  #[napi]
+  pub fn where_fn(
+    &self,
+    func_name: String,
+    args: Vec<String>,
+    operator: String,
+    value: Option<WhereValue>,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_fn(func_name, args, operator, value)
+  }
+
+  #[napi]
+  pub fn within(&self, min_x: f64, max_x: f64, min_y: f64, max_y: f64) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).within(min_x, max_x, min_y, max_y)
+  }
+
+  #[napi]
+  pub fn where_in_range(
+    &self,
+    column: String,
+    start: JsUnknown,
+    end: JsUnknown,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_in_range(column, start, end)
+  }
+
+  #[napi]
+  pub fn where_timestamp_between(
+    &self,
+    column: String,
+    start: JsUnknown,
+    end: JsUnknown,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_timestamp_between(column, start, end)
+  }
+
+  #[napi]
+  pub fn where_json_contains(
+    &self,
+    column: String,
+    path: String,
+    value: WhereValue,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_json_contains(column, path, value)
+  }
+
+  #[napi]
+  pub fn where_ilike(&self, column: String, pattern: String) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_ilike(column, pattern)
+  }
+
+  #[napi]
+  pub fn where_not(
+    &self,
+    env: Env,
+    column_or_group: napi::Either<String, JsFunction>,
+    op_or_value: Option<napi::Either<String, WhereValue>>,
+    value_opt: Option<WhereValue>,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).where_not(env, column_or_group, op_or_value, value_opt)
+  }
+
+  #[napi]
+  pub fn columns(&self) -> Result<Vec<String>> {
+    let conn = self.lock()?;
+    self.column_names(&conn)
+  }
+
+  #[napi]
+  pub fn column_info(&self) -> Result<Vec<ColumnInfo>> {
+    let conn = self.lock()?;
+    Ok(
+      table_column_meta(&self.schema, &conn, &self.name)?
+        .into_iter()
+        .filter(|column| column.hidden != 1)
+        .map(|column| ColumnInfo {
+          generated: column.generated().map(str::to_string),
+          name: column.name,
         })
+        .collect(),
+    )
+  }
+
+  // The CREATE statement SQLite stored for this table (or view), for snapshotting schemas
+  #[napi]
+  pub fn schema_sql(&self) -> Result<String> {
+    let (schema_prefix, name) = split_table_name(&self.name);
+    let conn = self.lock()?;
+    conn
+      .query_row(
+        &format!(
+          "SELECT sql FROM {}sqlite_master WHERE type IN ('table', 'view') AND name = ?",
+          schema_prefix
+        ),
+        [name],
+        |row| row.get::<_, Option<String>>(0),
+      )
+      .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+          napi::Error::from_reason(format!("Unknown table {}", self.name))
+        }
+        e => napi::Error::from_reason(e.to_string()),
+      })?
+      .ok_or_else(|| napi::Error::from_reason(format!("No schema SQL is stored for {}", self.name)))
+  }
+
+  #[napi]
+  pub fn rename_column(&self, old_name: String, new_name: String) -> Result<()> {
+    let conn = self.lock()?;
+    conn
+      .execute_batch(&format!(
+        "ALTER TABLE {} RENAME COLUMN {} TO {}",
+        quote_table_name(&self.name),
+        quote_identifier(&old_name),
+        quote_identifier(&new_name)
+      ))
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  #[napi]
+  pub fn add_column(&self, name: String, type_spec: String) -> Result<()> {
+    validate_type_spec(&type_spec)?;
+    let conn = self.lock()?;
+    conn
+      .execute_batch(&format!(
+        "ALTER TABLE {} ADD COLUMN {} {}",
+        quote_table_name(&self.name),
+        quote_identifier(&name),
+        type_spec.trim()
+      ))
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  #[napi]
+  pub fn drop_column(&self, name: String) -> Result<()> {
+    let conn = self.lock()?;
+    conn
+      .execute_batch(&format!(
+        "ALTER TABLE {} DROP COLUMN {}",
+        quote_table_name(&self.name),
+        quote_identifier(&name)
+      ))
+      .map_err(|e| napi::Error::from_reason(e.to_string()))
+  }
+
+  #[napi]
+  pub fn create_index(
+    &self,
+    env: Env,
+    columns: Vec<String>,
+    options: Option<IndexOptions>,
+  ) -> Result<String> {
+    if columns.is_empty() {
+      return Err(napi::Error::from_reason(
+        "createIndex needs at least one column",
+      ));
     }
-    
-    #[napi]
-    pub fn insert(&self, env: Env, data: JsUnknown) -> Result<()> {
-        let rows: Vec<HashMap<String, JsUnknown>> = if data.is_array()? {
-            let arr = data.coerce_to_object()?;
-            let length = arr.get_array_length()?;
-            let mut vec = Vec::with_capacity(length as usize);
-            for i in 0..length {
-                let item: JsUnknown = arr.get_element::<JsUnknown>(i)?;
-                let obj = item.coerce_to_object()?;
-                let map = js_object_to_hashmap(&env, &obj)?;
-                vec.push(map);
-            }
-            vec
-        } else {
-            let obj = data.coerce_to_object()?;
-            let map = js_object_to_hashmap(&env, &obj)?;
-            vec![map]
-        };
-
-        let mut conn = self.conn.lock().unwrap();
-        let tx = conn.transaction().map_err(|e| napi::Error::from_reason(e.to_string()))?;
-
-        for mut row in rows {
-            if row.is_empty() {
-                continue;
+    let (name, unique, where_clause) = match options {
+      Some(o) => (o.name, o.unique.unwrap_or(false), o.where_clause),
+      None => (None, false, None),
+    };
+    // An index lives in its table's schema, which qualifies the index name rather than the table
+    let (schema_prefix, table) = split_table_name(&self.name);
+    let name = name.unwrap_or_else(|| format!("idx_{}_{}", table, columns.join("_")));
+
+    let mut sql = format!(
+      "CREATE {}INDEX {}{} ON {} ({})",
+      if unique { "UNIQUE " } else { "" },
+      schema_prefix,
+      quote_identifier(&name),
+      quote_identifier(table),
+      columns
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ")
+    );
+
+    if let Some(where_clause) = where_clause {
+      let predicate = match where_clause.get_type()? {
+        ValueType::String => Some(where_clause.coerce_to_string()?.into_utf8()?.into_owned()?),
+        ValueType::Object if FilteredTable::instance_of(env, &where_clause)? => {
+          let filtered = unsafe {
+            ClassInstance::<FilteredTable>::from_napi_value(env.raw(), where_clause.raw())?
+          };
+          let mut params = Vec::new();
+          // Index DDL can't take bound parameters, so the condition values are inlined
+          filtered
+            .predicate(&mut params)
+            .map(|p| inline_params(&p, &params))
+            .transpose()?
+        }
+        ValueType::Undefined | ValueType::Null => None,
+        _ => {
+          return Err(napi::Error::from_reason(
+            "Index where must be a SQL string or a filtered query",
+          ))
+        }
+      };
+      if let Some(predicate) = predicate {
+        sql.push_str(" WHERE ");
+        sql.push_str(&predicate);
+      }
+    }
+
+    let conn = self.lock()?;
+    conn
+      .execute_batch(&sql)
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    Ok(name)
+  }
+
+  #[napi]
+  pub fn has_many(
+    &self,
+    name: String,
+    table: String,
+    foreign_key: String,
+    local_key: Option<String>,
+  ) -> Table {
+    self.add_relation(Relation {
+      name,
+      table,
+      related_column: foreign_key,
+      local_column: local_key.unwrap_or_else(|| "id".to_string()),
+    })
+  }
+
+  #[napi]
+  pub fn belongs_to(
+    &self,
+    name: String,
+    table: String,
+    foreign_key: String,
+    owner_key: Option<String>,
+  ) -> Table {
+    self.add_relation(Relation {
+      name,
+      table,
+      related_column: owner_key.unwrap_or_else(|| "id".to_string()),
+      local_column: foreign_key,
+    })
+  }
+
+  fn add_relation(&self, relation: Relation) -> Table {
+    let mut relations = self.relations.lock().unwrap();
+    let entry = relations.entry(self.name.clone()).or_default();
+    entry.retain(|r| r.name != relation.name);
+    entry.push(relation);
+    self.clone()
+  }
+
+  pub(crate) fn relation(&self, name: &str) -> Result<Relation> {
+    self
+      .relations
+      .lock()
+      .unwrap()
+      .get(&self.name)
+      .and_then(|relations| relations.iter().find(|r| r.name == name).cloned())
+      .ok_or_else(|| {
+        napi::Error::from_reason(format!("Unknown relation {} on table {}", name, self.name))
+      })
+  }
+
+  #[napi]
+  pub fn with_json(&self, relation: String) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).with_json(relation)
+  }
+
+  #[napi]
+  pub fn has(&self, relation: String) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).has(relation)
+  }
+
+  #[napi]
+  pub fn doesnt_have(&self, relation: String) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).doesnt_have(relation)
+  }
+
+  #[napi]
+  pub fn insert(
+    &self,
+    env: Env,
+    data: JsUnknown,
+    options: Option<InsertOptions>,
+  ) -> Result<Either3<i64, Vec<i64>, RunResult>> {
+    let (types, json_columns, result, debug, ignore_unknown, or_ignore) = match options {
+      Some(o) => {
+        let debug = o.debug.unwrap_or(false);
+        (
+          o.types.unwrap_or_default(),
+          o.json_columns,
+          o.result.unwrap_or(false) || debug,
+          debug,
+          o.ignore_unknown.unwrap_or(false),
+          o.or_ignore.unwrap_or(false),
+        )
+      }
+      None => (HashMap::new(), None, false, false, false, false),
+    };
+    let verb = if or_ignore {
+      "INSERT OR IGNORE"
+    } else {
+      "INSERT"
+    };
+
+    let is_array = data.is_array()?;
+    let mut rows: Vec<HashMap<String, JsUnknown>> = if is_array {
+      let arr = data.coerce_to_object()?;
+      let length = arr.get_array_length()?;
+      let mut vec = Vec::with_capacity(length as usize);
+      for i in 0..length {
+        let item: JsUnknown = arr.get_element::<JsUnknown>(i)?;
+        let obj = item.coerce_to_object()?;
+        let map = js_object_to_hashmap(&env, &obj, json_columns.as_deref())?;
+        vec.push(map);
+      }
+      vec
+    } else {
+      let obj = data.coerce_to_object()?;
+      let map = js_object_to_hashmap(&env, &obj, json_columns.as_deref())?;
+      vec![map]
+    };
+
+    let mut conn = self.lock()?;
+
+    let columns = table_column_meta(&self.schema, &conn, &self.name)?;
+    if ignore_unknown && columns.is_empty() {
+      return Err(napi::Error::from_reason(format!(
+        "Unknown table {}",
+        self.name
+      )));
+    }
+    // Generated columns can't be written, so values for them (e.g. from a row read back) are dropped
+    let generated: Vec<&str> = columns
+      .iter()
+      .filter(|c| c.generated().is_some())
+      .map(|c| c.name.as_str())
+      .collect();
+    for row in &mut rows {
+      row.retain(|column, _| {
+        !generated.contains(&column.as_str())
+          && (!ignore_unknown || columns.iter().any(|c| c.name == *column))
+      });
+    }
+
+    // A savepoint rather than BEGIN: inside a user's begin()/transaction() it nests, so a failed
+    // batch only undoes its own rows and leaves the outer transaction open for the caller
+    let tx = conn
+      .savepoint()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    let mut ids = Vec::with_capacity(rows.len());
+    let mut changes = 0;
+    // Only rows whose INSERT ran and changed nothing; skipped empty rows aren't counted
+    let mut ignored = 0;
+    let mut statements = Vec::new();
+    let mut bindings = Vec::new();
+    for mut row in rows {
+      // Rows left without values (empty, all null/undefined, or only unknown keys) aren't
+      // inserted, and like ignored rows get id 0
+      if row.is_empty() {
+        ids.push(0);
+        continue;
+      }
+      let columns: Vec<String> = row.keys().cloned().collect();
+      let sql = format!(
+        "{} INTO {} ({}) VALUES ({})",
+        verb,
+        self.name,
+        columns
+          .iter()
+          .map(|c| quote_identifier(c))
+          .collect::<Vec<_>>()
+          .join(", "),
+        vec!["?"; columns.len()].join(", ")
+      );
+
+      let mut stmt = tx
+        .prepare(&sql)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+      let values: Vec<BindValue> = columns
+        .iter()
+        .map(|col| {
+          let val = row
+            .remove(col)
+            .ok_or_else(|| napi::Error::from_reason(format!("Missing value for column {}", col)))?;
+          match types.get(col) {
+            Some(affinity) => {
+              js_unknown_to_typed_value(val, affinity, &self.config).map(BindValue::Value)
             }
-            let columns: Vec<String> = row.keys().cloned().collect();
-            let placeholders = vec!["?"; columns.len()].join(", ");
-            let sql = format!(
-                "INSERT INTO {} ({}) VALUES ({})",
-                self.name,
-                columns.join(", "),
-                placeholders
-            );
-
-            let mut stmt = tx.prepare(&sql).map_err(|e| napi::Error::from_reason(e.to_string()))?;
-
-            let values: Vec<rusqlite::types::Value> = columns
-                .iter()
-                .map(|col| {
-            let val = row
-                .remove(col)
-                .ok_or_else(|| napi::Error::from_reason(format!("Missing value for column {}", col)))?;
-            js_unknown_to_rusqlite_value(val)
+            None => js_unknown_to_bind_value(val, &self.config),
+          }
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-        stmt.execute(rusqlite::params_from_iter(values))
+      if debug {
+        statements.push(sql.clone());
+        bindings.extend(values.iter().map(BindValue::to_value));
+      }
+
+      let inserted = stmt
+        .execute(rusqlite::params_from_iter(values))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))? as i64;
+      changes += inserted;
+      if inserted == 0 {
+        ignored += 1;
+      }
+      // An ignored row has no id of its own; last_insert_rowid() would be a previous row's
+      ids.push(if inserted > 0 {
+        tx.last_insert_rowid()
+      } else {
+        0
+      });
+    }
+
+    tx.commit()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    if result {
+      Ok(Either3::C(RunResult {
+        changes,
+        last_insert_rowid: conn.last_insert_rowid(),
+        sql: debug.then(|| statements.join(";\n")),
+        bindings: debug.then(|| bindings_to_js(env, &bindings)).transpose()?,
+        ignored: or_ignore.then_some(ignored),
+      }))
+    } else if is_array {
+      Ok(Either3::B(ids))
+    } else {
+      Ok(Either3::A(ids[0]))
+    }
+  }
+
+  #[napi]
+  pub fn sync(&self, env: Env, key_column: String, rows: Vec<JsObject>) -> Result<SyncResult> {
+    let mut desired: Vec<(String, Vec<(String, rusqlite::types::Value)>)> =
+      Vec::with_capacity(rows.len());
+    for obj in rows {
+      let mut row = Vec::new();
+      for (col, val) in js_object_to_hashmap(&env, &obj, None)? {
+        row.push((col, js_unknown_to_rusqlite_value(val, &self.config)?));
+      }
+      let key = row
+        .iter()
+        .find(|(col, _)| *col == key_column)
+        .map(|(_, val)| value_key(val))
+        .ok_or_else(|| {
+          napi::Error::from_reason(format!("Row is missing key column {}", key_column))
+        })?;
+      desired.push((key, row));
+    }
+
+    let mut conn = self.lock()?;
+    let tx = conn
+      .savepoint()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    let mut existing: HashMap<String, HashMap<String, rusqlite::types::Value>> = HashMap::new();
+    {
+      let mut stmt = tx
+        .prepare(&format!("SELECT * FROM {}", self.name))
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+      if !column_names.contains(&key_column) {
+        return Err(napi::Error::from_reason(format!(
+          "Key column {} does not exist on {}",
+          key_column, self.name
+        )));
+      }
+      let mut query = stmt
+        .query([])
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      while let Some(row) = query
+        .next()
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?
+      {
+        let mut values = HashMap::new();
+        for (i, col) in column_names.iter().enumerate() {
+          let val: rusqlite::types::Value = row
+            .get(i)
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+          values.insert(col.clone(), val);
         }
+        existing.insert(value_key(&values[&key_column]), values);
+      }
+    }
 
-        tx.commit().map_err(|e| napi::Error::from_reason(e.to_string()))?;
-
-        Ok(())
-    }
-
-    #[napi]
-    pub fn create(&self, env: Env, data: JsUnknown) -> Result<()> {
-        self.insert(env, data)
-    }
-    
-    #[napi]
-    pub fn update(&self, id: napi::Either<String, i64>, data: JsObject) -> Result<()> {
-        FilteredTable {
-            table: self.clone(),
-            column: "id".to_string(),
-            operator: "=".to_string(),
-            value: id,
-            extra_conditions: vec![],
-            order_by: None,
-        }.update(data)
-    }
-
-    #[napi]
-    pub fn order_by(&self, column: String, direction: Option<String>) -> Result<FilteredTable> {
-        Ok(FilteredTable {
-            table: self.clone(),
-            column: "1".to_string(),
-            operator: "=".to_string(),
-            value: napi::Either::B(1),
-            extra_conditions: vec![],
-            order_by: Some((column, direction.unwrap_or("ASC".to_string()))),
-        })
+    let mut result = SyncResult {
+      inserted: 0,
+      updated: 0,
+      deleted: 0,
+    };
+    let mut seen = std::collections::HashSet::new();
+
+    for (key, row) in desired {
+      seen.insert(key.clone());
+      match existing.get(&key) {
+        None => {
+          let columns: Vec<String> = row.iter().map(|(col, _)| quote_identifier(col)).collect();
+          let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.name,
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+          );
+          tx.execute(
+            &sql,
+            rusqlite::params_from_iter(row.into_iter().map(|(_, val)| val)),
+          )
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+          result.inserted += 1;
+        }
+        Some(current) => {
+          let changed: Vec<(String, rusqlite::types::Value)> = row
+            .into_iter()
+            .filter(|(col, val)| {
+              *col != key_column && current.get(col).is_none_or(|cur| !values_equal(cur, val))
+            })
+            .collect();
+          if changed.is_empty() {
+            continue;
+          }
+          let set_clause = changed
+            .iter()
+            .map(|(col, _)| format!("{} = ?", quote_identifier(col)))
+            .collect::<Vec<_>>()
+            .join(", ");
+          let sql = format!(
+            "UPDATE {} SET {} WHERE {} = ?",
+            self.name,
+            set_clause,
+            quote_identifier(&key_column)
+          );
+          let mut values: Vec<rusqlite::types::Value> =
+            changed.into_iter().map(|(_, val)| val).collect();
+          values.push(current[&key_column].clone());
+          tx.execute(&sql, rusqlite::params_from_iter(values))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+          result.updated += 1;
+        }
+      }
     }
-    
-    #[napi]
-    pub fn destroy(&self, id: napi::Either<String, i64>) -> Result<()> {
-        FilteredTable {
-            table: self.clone(),
-            column: "id".to_string(),
-            operator: "=".to_string(),
-            value: id,
-            extra_conditions: vec![],
-            order_by: None,
-        }.destroy()
+
+    let sql = format!(
+      "DELETE FROM {} WHERE {} = ?",
+      self.name,
+      quote_identifier(&key_column)
+    );
+    for (key, current) in &existing {
+      if seen.contains(key) {
+        continue;
+      }
+      tx.execute(&sql, [&current[&key_column]])
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+      result.deleted += 1;
     }
-}
 
+    tx.commit()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
 
-impl Clone for Table {
-    fn clone(&self) -> Self {
-        Table {
-            name: self.name.clone(),
-            conn: self.conn.clone(),
-            //relations: self.relations.clone(),
+    Ok(result)
+  }
+
+  #[napi]
+  pub fn copy_to(
+    &self,
+    dest_table: String,
+    filter: Option<ClassInstance<FilteredTable>>,
+  ) -> Result<i64> {
+    if !is_valid_qualified_identifier(&dest_table) {
+      return Err(napi::Error::from_reason(format!(
+        "Invalid table name '{}': use letters, digits and _ (or schema.table)",
+        dest_table
+      )));
+    }
+    let mut sql = format!(
+      "INSERT INTO {} SELECT * FROM {}",
+      quote_table_name(&dest_table),
+      self.name
+    );
+    let mut params = Vec::new();
+    if let Some(filter) = filter {
+      if filter.table.name != self.name {
+        return Err(napi::Error::from_reason(format!(
+          "Filter targets {} but copy source is {}",
+          filter.table.name, self.name
+        )));
+      }
+      filter.build_conditions(&mut sql, &mut params);
+    }
+
+    let conn = self.lock()?;
+    let copied = conn
+      .execute(&sql, rusqlite::params_from_iter(params))
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    Ok(copied as i64)
+  }
+
+  #[napi]
+  pub fn upsert_many(
+    &self,
+    env: Env,
+    rows: Vec<JsObject>,
+    conflict_columns: Vec<String>,
+    update_columns: Option<Vec<String>>,
+    options: Option<UpsertOptions>,
+  ) -> Result<i64> {
+    if conflict_columns.is_empty() {
+      return Err(napi::Error::from_reason(
+        "upsertMany needs at least one conflict column",
+      ));
+    }
+    let Some(first) = rows.first() else {
+      return Ok(0);
+    };
+
+    // Every row shares one VALUES shape, so the column set is fixed by the first row
+    let mut columns: Vec<String> = js_object_to_hashmap(&env, first, None)?
+      .into_keys()
+      .collect();
+    columns.sort();
+    if columns.is_empty() {
+      return Err(napi::Error::from_reason(
+        "upsertMany rows need at least one column",
+      ));
+    }
+    let mut values = Vec::with_capacity(rows.len() * columns.len());
+    for row in &rows {
+      let mut map = js_object_to_hashmap(&env, row, None)?;
+      if map.len() != columns.len() || !columns.iter().all(|c| map.contains_key(c)) {
+        return Err(napi::Error::from_reason(
+          "All rows passed to upsertMany must have the same columns",
+        ));
+      }
+      for column in &columns {
+        let value = map.remove(column).expect("checked above");
+        values.push(js_unknown_to_bind_value(value, &self.config)?);
+      }
+    }
+
+    let mut update_columns = update_columns.unwrap_or_else(|| {
+      columns
+        .iter()
+        .filter(|c| !conflict_columns.contains(c))
+        .cloned()
+        .collect()
+    });
+    // SQLite rejects a column assigned twice in DO UPDATE SET
+    let mut listed = std::collections::HashSet::new();
+    update_columns.retain(|c| listed.insert(c.clone()));
+    let (where_clause, where_params) = match options {
+      Some(UpsertOptions {
+        where_clause: Some(sql),
+        bindings,
+      }) => {
+        if update_columns.is_empty() {
+          return Err(napi::Error::from_reason(
+            "upsertMany can only take a where condition with columns to update",
+          ));
         }
+        let params = bindings
+          .unwrap_or_default()
+          .into_iter()
+          .map(|v| js_unknown_to_bind_value(v, &self.config))
+          .collect::<Result<Vec<_>>>()?;
+        (format!(" WHERE {}", sql), params)
+      }
+      _ => (String::new(), Vec::new()),
+    };
+    let conflict_action = if update_columns.is_empty() {
+      "DO NOTHING".to_string()
+    } else {
+      format!(
+        "DO UPDATE SET {}{}",
+        update_columns
+          .iter()
+          .map(|c| format!("{0} = excluded.{0}", quote_identifier(c)))
+          .collect::<Vec<_>>()
+          .join(", "),
+        where_clause
+      )
+    };
+
+    let column_list = columns
+      .iter()
+      .map(|c| quote_identifier(c))
+      .collect::<Vec<_>>()
+      .join(", ");
+    let row_placeholders = format!("({})", vec!["?"; columns.len()].join(", "));
+    let conflict_list = conflict_columns
+      .iter()
+      .map(|c| quote_identifier(c))
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    let mut conn = self.lock()?;
+    let max_variables = conn
+      .limit(rusqlite::limits::Limit::SQLITE_LIMIT_VARIABLE_NUMBER)
+      .max(1) as usize;
+    // The condition's bindings follow every chunk's values
+    let rows_per_chunk = (max_variables.saturating_sub(where_params.len()) / columns.len()).max(1);
+
+    let tx = conn
+      .savepoint()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    let mut changes = 0;
+    for chunk in values.chunks(rows_per_chunk * columns.len()) {
+      let row_count = chunk.len() / columns.len();
+      let sql = format!(
+        "INSERT INTO {} ({}) VALUES {} ON CONFLICT ({}) {}",
+        self.name,
+        column_list,
+        vec![row_placeholders.as_str(); row_count].join(", "),
+        conflict_list,
+        conflict_action
+      );
+      changes += tx
+        .execute(
+          &sql,
+          rusqlite::params_from_iter(chunk.iter().chain(&where_params)),
+        )
+        .map_err(|e| napi::Error::from_reason(e.to_string()))? as i64;
+    }
+    tx.commit()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    Ok(changes)
+  }
+
+  // Inserts `data` unless a row with the same `unique_columns` exists, returning whichever
+  // row ends up stored. Both steps share a savepoint so a concurrent insert can't slip between them.
+  #[napi]
+  pub fn get_or_insert(
+    &self,
+    env: Env,
+    unique_columns: Vec<String>,
+    data: JsObject,
+  ) -> Result<JsObject> {
+    if unique_columns.is_empty() {
+      return Err(napi::Error::from_reason(
+        "getOrInsert needs at least one unique column",
+      ));
+    }
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+    for (column, value) in js_object_to_hashmap(&env, &data, None)? {
+      columns.push(column);
+      values.push(js_unknown_to_bind_value(value, &self.config)?);
+    }
+    let mut lookup = Vec::with_capacity(unique_columns.len());
+    for column in &unique_columns {
+      let Some(pos) = columns.iter().position(|c| c == column) else {
+        return Err(napi::Error::from_reason(format!(
+          "getOrInsert data is missing the unique column {}",
+          column
+        )));
+      };
+      lookup.push(&values[pos]);
+    }
+
+    let mut conn = self.lock()?;
+    let tx = conn
+      .savepoint()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+
+    let insert_sql = format!(
+      "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO NOTHING RETURNING *",
+      self.name,
+      columns
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", "),
+      vec!["?"; columns.len()].join(", "),
+      unique_columns
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", "),
+    );
+    let select_sql = format!(
+      "SELECT * FROM {} WHERE {}",
+      self.name,
+      unique_columns
+        .iter()
+        .map(|c| format!("{} = ?", quote_identifier(c)))
+        .collect::<Vec<_>>()
+        .join(" AND "),
+    );
+
+    let row = {
+      let fetch = |sql: &str, params: &[&BindValue]| -> Result<Option<JsObject>> {
+        let mut stmt = tx
+          .prepare(sql)
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+        let mut rows = stmt
+          .query(rusqlite::params_from_iter(params))
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        rows
+          .next()
+          .map_err(|e| napi::Error::from_reason(e.to_string()))?
+          .map(|row| row_to_object(env, row, &column_names, &self.config))
+          .transpose()
+          .map_err(|e| napi::Error::from_reason(e.to_string()))
+      };
+      match fetch(&insert_sql, &values.iter().collect::<Vec<_>>())? {
+        Some(row) => row,
+        None => fetch(&select_sql, &lookup)?.ok_or_else(|| {
+          napi::Error::from_reason("getOrInsert conflicted on a row it could not select back")
+        })?,
+      }
+    };
+
+    tx.commit()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    Ok(row)
+  }
+
+  #[napi]
+  pub fn count(&self) -> Result<napi::Either<i64, BigInt>> {
+    FilteredTable::new(self.clone()).count()
+  }
+
+  #[napi]
+  pub fn sum(&self, column: String) -> Result<Option<f64>> {
+    FilteredTable::new(self.clone()).sum(column)
+  }
+
+  #[napi]
+  pub fn avg(&self, column: String) -> Result<Option<f64>> {
+    FilteredTable::new(self.clone()).avg(column)
+  }
+
+  #[napi]
+  pub fn min(&self, column: String) -> Result<Option<f64>> {
+    FilteredTable::new(self.clone()).min(column)
+  }
+
+  #[napi]
+  pub fn max(&self, column: String) -> Result<Option<f64>> {
+    FilteredTable::new(self.clone()).max(column)
+  }
+
+  #[napi]
+  pub fn count_distinct(&self, column: String) -> Result<napi::Either<i64, BigInt>> {
+    FilteredTable::new(self.clone()).count_distinct(column)
+  }
+
+  #[napi]
+  pub fn lock_for_update(&self, env: Env) -> Result<Vec<JsObject>> {
+    FilteredTable::new(self.clone()).lock_for_update(env)
+  }
+
+  #[napi]
+  pub fn reduce(&self, env: Env, initial: JsUnknown, reducer: JsFunction) -> Result<JsUnknown> {
+    FilteredTable::new(self.clone()).reduce(env, initial, reducer)
+  }
+
+  #[napi]
+  pub fn to_csv(&self) -> Result<String> {
+    FilteredTable::new(self.clone()).to_csv()
+  }
+
+  #[napi]
+  pub fn stream_csv(&self, callback: JsFunction, options: Option<CsvOptions>) -> Result<i64> {
+    FilteredTable::new(self.clone()).stream_csv(callback, options)
+  }
+
+  #[napi]
+  pub fn create(
+    &self,
+    env: Env,
+    data: JsUnknown,
+    options: Option<InsertOptions>,
+  ) -> Result<Either3<i64, Vec<i64>, RunResult>> {
+    self.insert(env, data, options)
+  }
+
+  #[napi]
+  pub fn update(
+    &self,
+    env: Env,
+    id: napi::Either<String, i64>,
+    data: JsObject,
+    options: Option<WriteOptions>,
+  ) -> Result<WriteResult> {
+    FilteredTable::new(self.clone())
+      .with_condition("id", "=", id_to_value(id))
+      .update(env, data, options)
+  }
+
+  #[napi]
+  pub fn latest(&self, column: Option<String>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).latest(column)
+  }
+
+  #[napi]
+  pub fn oldest(&self, column: Option<String>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).oldest(column)
+  }
+
+  #[napi]
+  pub fn order_by_safe(
+    &self,
+    column: String,
+    direction: Option<String>,
+    allowed_columns: Vec<String>,
+  ) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).order_by_safe(column, direction, allowed_columns)
+  }
+
+  #[napi]
+  pub fn order_by(&self, column: String, direction: Option<String>) -> Result<FilteredTable> {
+    FilteredTable::new(self.clone()).order_by(column, direction)
+  }
+
+  #[napi]
+  pub fn destroy(
+    &self,
+    env: Env,
+    id: napi::Either<String, i64>,
+    options: Option<WriteOptions>,
+  ) -> Result<WriteResult> {
+    FilteredTable::new(self.clone())
+      .with_condition("id", "=", id_to_value(id))
+      .destroy(env, options)
+  }
+}
+
+impl Clone for Table {
+  fn clone(&self) -> Self {
+    Table {
+      name: self.name.clone(),
+      conn: self.conn.clone(),
+      interrupt: self.interrupt.clone(),
+      closed: self.closed.clone(),
+      config: self.config.clone(),
+      relations: self.relations.clone(),
+      ctes: self.ctes.clone(),
+      cache: self.cache.clone(),
+      schema: self.schema.clone(),
+      alias: self.alias.clone(),
+      temp: self.temp.clone(),
     }
+  }
 }
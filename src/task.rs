@@ -0,0 +1,169 @@
+use napi::{Env, JsBoolean, JsFunction, JsObject, JsUnknown, Result, Task};
+use napi_derive::napi;
+use rusqlite::{Connection, InterruptHandle};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+use crate::cache::{cached_query, cached_rows_to_objects, query_values, CachedRows, SharedCache};
+use crate::extra::{lock_connection, Config, OwnedParams};
+
+#[napi(object)]
+pub struct AsyncQueryOptions {
+  // An AbortSignal; aborting rejects the promise and interrupts the query if it is running
+  pub signal: Option<JsObject>,
+}
+
+pub(crate) fn check_signal(signal: Option<&JsObject>) -> Result<()> {
+  let Some(signal) = signal else {
+    return Ok(());
+  };
+  if signal
+    .get_named_property::<JsBoolean>("aborted")?
+    .get_value()?
+  {
+    return Err(aborted());
+  }
+  Ok(())
+}
+
+fn aborted() -> napi::Error {
+  napi::Error::new(napi::Status::Cancelled, "The operation was aborted")
+}
+
+#[derive(Default)]
+struct AbortState {
+  aborted: bool,
+  running: bool,
+}
+
+// A read query run on the libuv threadpool. The connection lock is only taken on the worker, so
+// synchronous calls made meanwhile wait for the query instead of the event loop waiting for them.
+pub struct QueryTask {
+  conn: Arc<Mutex<Connection>>,
+  closed: Arc<AtomicBool>,
+  cache: SharedCache,
+  config: Config,
+  sql: String,
+  params: Option<OwnedParams>,
+  // Columns holding JSON text that resolve parses, for FilteredTable::withJson relations
+  json_columns: Vec<String>,
+  state: Arc<Mutex<AbortState>>,
+}
+
+impl QueryTask {
+  pub(crate) fn new(
+    env: Env,
+    // The interrupt handle comes along so aborting never needs the lock on the JS thread
+    (conn, interrupt, closed): (
+      Arc<Mutex<Connection>>,
+      Arc<InterruptHandle>,
+      Arc<AtomicBool>,
+    ),
+    cache: SharedCache,
+    config: Config,
+    (sql, params): (String, OwnedParams),
+    json_columns: Vec<String>,
+    signal: Option<JsObject>,
+  ) -> Result<Self> {
+    check_signal(signal.as_ref())?;
+    let state = Arc::new(Mutex::new(AbortState::default()));
+    if let Some(signal) = signal {
+      watch_signal(env, &signal, state.clone(), interrupt)?;
+    }
+    Ok(QueryTask {
+      conn,
+      closed,
+      cache,
+      config,
+      sql,
+      params: Some(params),
+      json_columns,
+      state,
+    })
+  }
+}
+
+// Only a query that is still running gets interrupted, so a late abort can't cut short a query
+// started by someone else on the same connection
+fn watch_signal(
+  env: Env,
+  signal: &JsObject,
+  state: Arc<Mutex<AbortState>>,
+  interrupt: Arc<InterruptHandle>,
+) -> Result<()> {
+  let listener = env.create_function_from_closure("onabort", move |ctx| {
+    let mut state = state.lock().unwrap();
+    state.aborted = true;
+    if state.running {
+      interrupt.interrupt();
+    }
+    ctx.env.get_undefined()
+  })?;
+  let mut once = env.create_object()?;
+  once.set_named_property("once", true)?;
+  let add = signal.get_named_property::<JsFunction>("addEventListener")?;
+  add.call(
+    Some(signal),
+    &[
+      env.create_string("abort")?.into_unknown(),
+      listener.into_unknown(),
+      once.into_unknown(),
+    ],
+  )?;
+  Ok(())
+}
+
+impl Task for QueryTask {
+  type Output = Arc<CachedRows>;
+  type JsValue = Vec<JsObject>;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let params = self.params.take().expect("compute runs once").into();
+    let conn = lock_connection(&self.conn, &self.closed)?;
+    {
+      let mut state = self.state.lock().unwrap();
+      if state.aborted {
+        return Err(aborted());
+      }
+      state.running = true;
+    }
+    let result = match &self.cache {
+      Some(cache) => cached_query(cache, &conn, &self.sql, params, false),
+      None => query_values(&conn, &self.sql, params, false).map(|(rows, _)| Arc::new(rows)),
+    };
+    let mut state = self.state.lock().unwrap();
+    state.running = false;
+    if state.aborted {
+      return Err(aborted());
+    }
+    result
+  }
+
+  fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    let rows = cached_rows_to_objects(env, &output, &self.config)?;
+    parse_json_columns(env, rows, &self.json_columns)
+  }
+}
+
+pub(crate) fn parse_json_columns(
+  env: Env,
+  rows: Vec<JsObject>,
+  columns: &[String],
+) -> Result<Vec<JsObject>> {
+  if columns.is_empty() {
+    return Ok(rows);
+  }
+  let json = env.get_global()?.get_named_property::<JsObject>("JSON")?;
+  let parse = json.get_named_property::<JsFunction>("parse")?;
+  rows
+    .into_iter()
+    .map(|mut row| {
+      for name in columns {
+        let raw = row.get_named_property::<JsUnknown>(name)?;
+        let parsed = parse.call(Some(&json), &[raw])?;
+        row.set_named_property(name, parsed)?;
+      }
+      Ok(row)
+    })
+    .collect()
+}